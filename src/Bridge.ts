@@ -17,7 +17,7 @@ import {
   PLManager,
 } from "matrix-bot-sdk";
 import BotUsersManager from "./managers/BotUsersManager";
-import { BridgeConfig } from "./config/Config";
+import { BridgeConfig, PermissionOverridesStateType } from "./config/Config";
 import { BridgePermissionLevel } from "./config/BridgePermissionLevel";
 import { BridgeWidgetApi } from "./widgets/BridgeWidgetApi";
 import { CommentProcessor } from "./CommentProcessor";
@@ -27,6 +27,7 @@ import { GithubInstance } from "./github/GithubInstance";
 import { IBridgeStorageProvider } from "./stores/StorageProvider";
 import {
   IConnection,
+  PermissionCheckFn,
   GitHubDiscussionSpace,
   GitHubDiscussionConnection,
   GitHubUserSpace,
@@ -1390,8 +1391,16 @@ export class Bridge {
     }
 
     const adminRoom = this.adminRooms.get(roomId);
-    const checkPermission = (service: string, level: BridgePermissionLevel) =>
-      this.config.checkPermission(event.sender, service, level);
+    const checkPermission =
+      (target?: string): PermissionCheckFn =>
+      (service, level) =>
+        this.config.checkPermissionInRoom(
+          event.sender,
+          roomId,
+          service,
+          level,
+          target,
+        );
 
     if (!adminRoom) {
       let handled = false;
@@ -1413,7 +1422,7 @@ export class Bridge {
           if (connection.onMessageEvent) {
             handled = await connection.onMessageEvent(
               event,
-              checkPermission,
+              checkPermission(connection.permissionTarget),
               replyEvent?.raw,
             );
           }
@@ -1469,7 +1478,7 @@ export class Bridge {
             );
             const handled = await setupConnection.onMessageEvent(
               event,
-              checkPermission,
+              checkPermission(),
             );
             if (handled) {
               break;
@@ -1642,6 +1651,22 @@ export class Bridge {
         return;
       }
 
+      if (
+        event.type === PermissionOverridesStateType &&
+        event.state_key === ""
+      ) {
+        this.config.updateRoomPermissionOverrides(roomId, event.content);
+        return;
+      }
+
+      if (event.type === "m.space.child") {
+        this.config
+          .refreshSpaceChildren(this.as.botClient, roomId)
+          .catch((ex) => {
+            log.warn(`Failed to refresh space children for ${roomId}`, ex);
+          });
+      }
+
       if (event.type === "m.room.tombstone" && event.state_key === "") {
         const replacementRoomId = event.content.replacement_room;
         if (typeof replacementRoomId !== "string") {
@@ -1655,6 +1680,7 @@ export class Bridge {
       }
 
       if (event.type === "m.room.power_levels" && event.state_key === "") {
+        this.config.updatePowerLevelsCache(roomId, event.content);
         this.connectionManager.checkAndMigrateIfPendingUpgrade(
           roomId,
           "power level",
@@ -4,7 +4,7 @@
 
 import { Appservice, Intent, MatrixError, StateEvent } from "matrix-bot-sdk";
 import { ApiError, ErrCode } from "./api";
-import { BridgeConfig } from "./config/Config";
+import { BridgeConfig, PermissionOverridesStateType } from "./config/Config";
 import { BridgePermissionLevel } from "./config/BridgePermissionLevel";
 import { CommentProcessor } from "./CommentProcessor";
 import {
@@ -111,10 +111,12 @@ export class ConnectionManager extends EventEmitter {
     );
     if (connectionType?.provisionConnection) {
       if (
-        !this.config.checkPermission(
+        !this.config.checkPermissionInRoom(
           userId,
+          roomId,
           connectionType.ServiceCategory,
           BridgePermissionLevel.manageConnections,
+          connectionType.getPermissionTarget?.(data),
         )
       ) {
         throw new ApiError(
@@ -210,12 +212,17 @@ export class ConnectionManager extends EventEmitter {
     state: StateEvent,
     serviceType: string,
   ) {
+    const target = this.getConnectionTypeForEventType(
+      state.type,
+    )?.getPermissionTarget?.(state.content as Record<string, unknown>);
     return (
       this.botUsersManager.isBotUser(state.sender) ||
-      this.config.checkPermission(
+      this.config.checkPermissionInRoom(
         state.sender,
+        roomId,
         serviceType,
         BridgePermissionLevel.manageConnections,
+        target,
       )
     );
   }
@@ -394,6 +401,13 @@ export class ConnectionManager extends EventEmitter {
       retryMatrixErrorFilter,
     );
 
+    // Overrides must be known before checking whether state is allowed.
+    const overrides = state.find(
+      (event) =>
+        event.type === PermissionOverridesStateType && event.state_key === "",
+    );
+    this.config.updateRoomPermissionOverrides(roomId, overrides?.content);
+
     for (const event of state) {
       try {
         const conn = await this.createConnectionForState(
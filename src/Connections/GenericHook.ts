@@ -482,6 +482,10 @@ export class GenericHookConnection
   static readonly ServiceCategory = ConnectionType.Generic;
   static readonly SupportsStaticConfiguration = true;
 
+  static getPermissionTarget(data: Record<string, unknown>) {
+    return typeof data.name === "string" ? `generic:${data.name}` : undefined;
+  }
+
   static readonly EventTypes = [
     GenericHookConnection.CanonicalEventType,
     GenericHookConnection.LegacyEventType,
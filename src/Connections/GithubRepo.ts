@@ -582,6 +582,13 @@ export class GitHubRepoConnection
   static readonly ServiceCategory = ConnectionType.Github;
   static readonly QueryRoomRegex = /#github_(.+)_(.+):.*/;
 
+  static getPermissionTarget(data: Record<string, unknown>) {
+    if (typeof data.org !== "string" || typeof data.repo !== "string") {
+      return undefined;
+    }
+    return `github:${data.org}/${data.repo}`.toLowerCase();
+  }
+
   static async createConnectionForState(
     roomId: string,
     state: StateEvent<Record<string, unknown>>,
@@ -756,6 +763,10 @@ export class GitHubRepoConnection
     return this.state.repo.toLowerCase();
   }
 
+  public get permissionTarget() {
+    return `github:${this.org}/${this.repo}`;
+  }
+
   public get priority(): number {
     return this.state.priority || super.priority;
   }
@@ -207,6 +207,13 @@ export class GitLabRepoConnection
   static helpMessage: (cmdPrefix?: string | undefined) => MatrixMessageContent;
   static ServiceCategory = ConnectionType.Gitlab;
 
+  static getPermissionTarget(data: Record<string, unknown>) {
+    if (typeof data.path !== "string") {
+      return undefined;
+    }
+    return `gitlab:${data.path}`.toLowerCase();
+  }
+
   static validateState(
     state: unknown,
     isExistingState = false,
@@ -604,6 +611,10 @@ export class GitLabRepoConnection
     return this.state.path.toLowerCase();
   }
 
+  public get permissionTarget() {
+    return `gitlab:${this.path}`;
+  }
+
   /**
    * The project's path string as returned by GitLab,
    * with the letter casing of the path that the
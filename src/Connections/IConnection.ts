@@ -42,6 +42,12 @@ export interface IConnection {
    */
   isStatic?: boolean;
 
+  /**
+   * The target that permissions for this connection are checked against, such as
+   * `github:matrix-org/matrix-hookshot`.
+   */
+  permissionTarget?: string;
+
   /**
    * Ensures that the current state loaded into the connection has been granted by
    * the remote service. I.e. If the room is bridged into a GitHub repository,
@@ -125,6 +131,10 @@ export type ConnectionDeclaration<C extends IConnection = IConnection> =
 interface ConnectionDeclarationBase<C extends IConnection = IConnection> {
   EventTypes: string[];
   ServiceCategory: ConnectionType;
+  /**
+   * The permission target for a connection's provisioning data, if the service has targets.
+   */
+  getPermissionTarget?: (data: Record<string, unknown>) => string | undefined;
   provisionConnection?: (
     roomId: string,
     userId: string,
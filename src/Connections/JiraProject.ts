@@ -97,6 +97,15 @@ export class JiraProjectConnection
     JiraProjectConnection.LegacyEventType,
   ];
   static readonly ServiceCategory = ConnectionType.Jira;
+
+  static getPermissionTarget(data: Record<string, unknown>) {
+    if (typeof data.url !== "string" || !URL.canParse(data.url)) {
+      return undefined;
+    }
+    const projectUrl = new URL(data.url);
+    const projectKey = JiraProjectConnection.getProjectKeyForUrl(projectUrl);
+    return projectKey ? `jira:${projectUrl.host}/${projectKey}` : undefined;
+  }
   static botCommands: BotCommands;
   static helpMessage: (cmdPrefix?: string) => MatrixMessageContent;
 
@@ -264,6 +273,12 @@ export class JiraProjectConnection
       : undefined;
   }
 
+  public get permissionTarget() {
+    return this.projectUrl && this.projectKey
+      ? `jira:${this.projectUrl.host}/${this.projectKey}`
+      : undefined;
+  }
+
   public static getProjectKeyForUrl(projectUrl: URL) {
     const parts = projectUrl?.pathname.split("/");
     return parts ? parts[parts.length - 1]?.toUpperCase() : undefined;
@@ -112,10 +112,22 @@ export class SetupConnection extends CommandConnection {
       );
     }
 
+    const urlParts =
+      /^https:\/\/github\.com\/([A-Za-z0-9_.-]+)\/([A-Za-z0-9_.-]+)$/.exec(
+        url.trim().toLowerCase(),
+      );
+    if (!urlParts) {
+      throw new CommandError(
+        "Invalid GitHub url",
+        "The GitHub url you entered was not valid.",
+      );
+    }
+    const [, org, repo] = urlParts;
     await this.checkUserPermissions(
       userId,
       GitHubRepoConnection.ServiceCategory,
       GitHubRepoConnection.CanonicalEventType,
+      GitHubRepoConnection.getPermissionTarget({ org, repo }),
     );
     const octokit =
       await this.provisionOpts.tokenStore.getOctokitForUser(userId);
@@ -125,17 +137,6 @@ export class SetupConnection extends CommandConnection {
         "You are not logged into GitHub. Start a DM with this bot and use the command `github login`.",
       );
     }
-    const urlParts =
-      /^https:\/\/github\.com\/([A-Za-z0-9_.-]+)\/([A-Za-z0-9_.-]+)$/.exec(
-        url.trim().toLowerCase(),
-      );
-    if (!urlParts) {
-      throw new CommandError(
-        "Invalid GitHub url",
-        "The GitHub url you entered was not valid.",
-      );
-    }
-    const [, org, repo] = urlParts;
     const { connection } = await GitHubRepoConnection.provisionConnection(
       this.roomId,
       userId,
@@ -163,12 +164,6 @@ export class SetupConnection extends CommandConnection {
       );
     }
 
-    await this.checkUserPermissions(
-      userId,
-      GitLabRepoConnection.ServiceCategory,
-      GitLabRepoConnection.CanonicalEventType,
-    );
-
     const { name, instance } =
       this.config.gitlab.getInstanceByProjectUrl(url) || {};
     if (!instance || !name) {
@@ -177,6 +172,20 @@ export class SetupConnection extends CommandConnection {
         "No instance found that matches the provided URL.",
       );
     }
+    const path = url.slice(instance.url.length + 1);
+    if (!path) {
+      throw new CommandError(
+        "Invalid GitLab url",
+        "The GitLab project url you entered was not valid.",
+      );
+    }
+
+    await this.checkUserPermissions(
+      userId,
+      GitLabRepoConnection.ServiceCategory,
+      GitLabRepoConnection.CanonicalEventType,
+      GitLabRepoConnection.getPermissionTarget({ path }),
+    );
 
     const client = await this.provisionOpts.tokenStore.getGitLabForUser(
       userId,
@@ -188,13 +197,6 @@ export class SetupConnection extends CommandConnection {
         "You are not logged into this GitLab instance. Start a DM with this bot and use the command `gitlab personaltoken`.",
       );
     }
-    const path = url.slice(instance.url.length + 1);
-    if (!path) {
-      throw new CommandError(
-        "Invalid GitLab url",
-        "The GitLab project url you entered was not valid.",
-      );
-    }
     const { connection, warning } =
       await GitLabRepoConnection.provisionConnection(
         this.roomId,
@@ -250,13 +252,14 @@ export class SetupConnection extends CommandConnection {
       );
     }
 
+    const safeUrl = await this.getJiraProjectSafeUrl(urlStr);
     await this.checkUserPermissions(
       userId,
       JiraProjectConnection.ServiceCategory,
       JiraProjectConnection.CanonicalEventType,
+      JiraProjectConnection.getPermissionTarget({ url: safeUrl }),
     );
     await this.checkJiraLogin(userId, urlStr);
-    const safeUrl = await this.getJiraProjectSafeUrl(urlStr);
 
     const res = await JiraProjectConnection.provisionConnection(
       this.roomId,
@@ -326,13 +329,14 @@ export class SetupConnection extends CommandConnection {
     category: JiraProjectConnection.ServiceCategory,
   })
   public async onJiraRemoveProject(userId: string, urlStr: string) {
+    const safeUrl = await this.getJiraProjectSafeUrl(urlStr);
     await this.checkUserPermissions(
       userId,
       JiraProjectConnection.ServiceCategory,
       JiraProjectConnection.CanonicalEventType,
+      JiraProjectConnection.getPermissionTarget({ url: safeUrl }),
     );
     await this.checkJiraLogin(userId, urlStr);
-    const safeUrl = await this.getJiraProjectSafeUrl(urlStr);
 
     const eventTypes = [
       JiraProjectConnection.CanonicalEventType,
@@ -401,6 +405,7 @@ export class SetupConnection extends CommandConnection {
       userId,
       GenericHookConnection.ServiceCategory,
       GenericHookConnection.CanonicalEventType,
+      GenericHookConnection.getPermissionTarget({ name }),
     );
 
     if (!name || name.length < 3 || name.length > 64) {
@@ -494,6 +499,7 @@ export class SetupConnection extends CommandConnection {
       userId,
       GenericHookConnection.ServiceCategory,
       GenericHookConnection.CanonicalEventType,
+      GenericHookConnection.getPermissionTarget({ name }),
     );
 
     const event = await this.client
@@ -987,12 +993,15 @@ export class SetupConnection extends CommandConnection {
     userId: string,
     service: string,
     stateEventType: string,
+    target?: string,
   ): Promise<void> {
     if (
-      !this.config.checkPermission(
+      !this.config.checkPermissionInRoom(
         userId,
+        this.roomId,
         service,
         BridgePermissionLevel.manageConnections,
+        target,
       )
     ) {
       throw new CommandError(
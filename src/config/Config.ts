@@ -10,7 +10,13 @@ import {
 import * as assert from "assert";
 import { configKey, hideKey } from "./Decorators";
 import { BridgeConfigListener, ResourceTypeArray } from "../ListenerService";
-import { BridgeConfigActorPermission, BridgePermissions } from "../libRs";
+import {
+  BridgeConfigActorPermission,
+  BridgeConfigPermissionRole,
+  BridgePermissions,
+  PermissionDiagnosticSeverity,
+  validatePermissionConfig,
+} from "../libRs";
 import { ConfigError } from "../Errors";
 import { ApiError, ErrCode } from "../api";
 import { Logger } from "matrix-appservice-bridge";
@@ -45,6 +51,16 @@ import { ConnectionType } from "../Connections/type";
 
 const log = new Logger("Config");
 
+/**
+ * Room state event containing permission rules which narrow the
+ * config permissions within that room.
+ */
+export const PermissionOverridesStateType =
+  "uk.half-shot.matrix-hookshot.permissions";
+
+// Warn about permissions that will expire within a week.
+const EXPIRING_PERMISSION_WARN_MS = 7 * 24 * 60 * 60 * 1000;
+
 export const ValidLogLevelStrings = [
   LogLevel.ERROR.toString(),
   LogLevel.WARN.toString(),
@@ -137,6 +153,7 @@ export interface BridgeConfigRoot {
   metrics?: BridgeConfigMetrics;
   passFile: string;
   permissions?: BridgeConfigActorPermission[];
+  permissionRoles?: Record<string, BridgeConfigPermissionRole>;
   openProject?: BridgeOpenProjectConfigYAML;
   queue?: BridgeConfigQueue;
   sentry?: BridgeConfigSentry;
@@ -176,6 +193,11 @@ export class BridgeConfig {
     true,
   )
   public readonly permissions: BridgeConfigActorPermission[];
+  @configKey(
+    `Named roles that permission entries can reference. See docs/setup.md#permissions for help`,
+    true,
+  )
+  public readonly permissionRoles?: Record<string, BridgeConfigPermissionRole>;
   @configKey(`A passkey used to encrypt tokens stored inside the bridge.
  Run openssl genpkey -out passkey.pem -outform PEM -algorithm RSA -pkeyopt rsa_keygen_bits:4096 to generate`)
   public readonly passFile: string;
@@ -344,7 +366,33 @@ export class BridgeConfig {
         ],
       },
     ];
-    this.bridgePermissions = new BridgePermissions(this.permissions);
+    this.permissionRoles = configData.permissionRoles;
+    for (const diagnostic of validatePermissionConfig(
+      this.permissions,
+      this.permissionRoles,
+    )) {
+      const path =
+        diagnostic.role !== undefined
+          ? `permissionRoles.${diagnostic.role}.${diagnostic.field}`
+          : `permissions[${diagnostic.entryIndex}].${diagnostic.field}`;
+      if (diagnostic.severity === PermissionDiagnosticSeverity.Error) {
+        throw new ConfigError(path, diagnostic.message);
+      }
+      log.warn(`Permission config (${path}): ${diagnostic.message}`);
+    }
+    this.bridgePermissions = new BridgePermissions(
+      this.permissions,
+      this.permissionRoles,
+    );
+    for (const grant of this.getExpiringPermissions(
+      EXPIRING_PERMISSION_WARN_MS,
+    )) {
+      log.warn(
+        grant.expired
+          ? `Permission for ${grant.actor} (permissions[${grant.entryIndex}]) expired at ${grant.expires} and can be removed.`
+          : `Permission for ${grant.actor} (permissions[${grant.entryIndex}]) will expire at ${grant.expires}.`,
+      );
+    }
 
     if (!configData.permissions) {
       log.warn(
@@ -468,14 +516,56 @@ export class BridgeConfig {
       `Prefilling room membership for permissions for ${permissionRooms.length} rooms`,
     );
     for (const roomEntry of permissionRooms) {
-      const roomId = await client.resolveRoom(roomEntry);
-      // Attempt to join the room
-      await client.joinRoom(roomEntry);
-      const membership = await client.getJoinedRoomMembers(roomId);
-      membership.forEach((userId) =>
-        this.bridgePermissions.addMemberToCache(roomEntry, userId),
-      );
-      log.debug(`Found ${membership.length} users for ${roomEntry}`);
+      await this.prefillRoomMembership(client, roomEntry);
+    }
+    for (const spaceId of this.bridgePermissions.getInterestedSpaces()) {
+      await this.refreshSpaceChildren(client, spaceId);
+    }
+  }
+
+  private async prefillRoomMembership(client: MatrixClient, roomEntry: string) {
+    const roomId = await client.resolveRoom(roomEntry);
+    // Attempt to join the room
+    await client.joinRoom(roomEntry);
+    const membership = await client.getJoinedRoomMembers(roomId);
+    membership.forEach((userId) =>
+      this.bridgePermissions.addMemberToCache(roomEntry, userId),
+    );
+    log.debug(`Found ${membership.length} users for ${roomEntry}`);
+    const powerLevels = await client
+      .getRoomStateEventContent(roomId, "m.room.power_levels", "")
+      .catch((ex) => {
+        log.warn(`Could not fetch power levels for ${roomEntry}`, ex);
+        return undefined;
+      });
+    if (powerLevels) {
+      this.updatePowerLevelsCache(roomEntry, powerLevels);
+    }
+  }
+
+  /**
+   * Fetch the child rooms of a space used for permissions, and start
+   * tracking the membership of any new children.
+   */
+  public async refreshSpaceChildren(client: MatrixClient, spaceId: string) {
+    if (!this.bridgePermissions.getInterestedSpaces().includes(spaceId)) {
+      return;
+    }
+    const space = await client.getSpace(spaceId);
+    const children = Object.keys(await space.getChildEntities());
+    const { addedRooms } = this.bridgePermissions.setSpaceChildren(
+      spaceId,
+      children,
+    );
+    log.info(
+      `Found ${children.length} child rooms for ${spaceId}, ${addedRooms.length} are new`,
+    );
+    for (const roomId of addedRooms) {
+      try {
+        await this.prefillRoomMembership(client, roomId);
+      } catch (ex) {
+        log.warn(`Could not fetch membership for ${roomId} in ${spaceId}`, ex);
+      }
     }
   }
 
@@ -487,6 +577,66 @@ export class BridgeConfig {
     this.bridgePermissions.removeMemberFromCache(roomId, userId);
   }
 
+  public updatePowerLevelsCache(roomId: string, content: unknown) {
+    try {
+      this.bridgePermissions.updatePowerLevels(roomId, content);
+    } catch (ex) {
+      log.warn(`Could not update power levels cache for ${roomId}`, ex);
+    }
+  }
+
+  /**
+   * Get permission entries which will expire within `withinMs`, including
+   * those which have already expired.
+   */
+  public getExpiringPermissions(withinMs: number) {
+    return this.bridgePermissions.getExpiringPermissions(withinMs);
+  }
+
+  /**
+   * Update the permission overrides for a room from the content of its
+   * overrides state event. Invalid overrides deny everything in the room,
+   * rather than being ignored.
+   */
+  public updateRoomPermissionOverrides(
+    roomId: string,
+    content?: Record<string, unknown>,
+  ) {
+    const permissions = content?.permissions;
+    if (permissions === undefined) {
+      this.bridgePermissions.setRoomOverrides(roomId);
+      return;
+    }
+    try {
+      if (!Array.isArray(permissions)) {
+        throw Error("'permissions' must be an array");
+      }
+      this.bridgePermissions.setRoomOverrides(roomId, permissions);
+    } catch (ex) {
+      log.warn(
+        `Invalid permission overrides in ${roomId}, denying all actions in the room`,
+        ex,
+      );
+      this.bridgePermissions.setRoomOverrides(roomId, []);
+    }
+  }
+
+  public checkPermissionInRoom(
+    mxid: string,
+    roomId: string,
+    service: string,
+    permission: BridgePermissionLevel,
+    target?: string,
+  ) {
+    return this.bridgePermissions.checkActionInRoom(
+      mxid,
+      roomId,
+      service,
+      BridgePermissionLevel[permission],
+      target,
+    );
+  }
+
   public checkPermissionAny(mxid: string, permission: BridgePermissionLevel) {
     return this.bridgePermissions.checkActionAny(
       mxid,
@@ -1,20 +1,179 @@
 use std::collections::{HashMap, HashSet};
 
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+use ruma::events::room::power_levels::RoomPowerLevelsEventContent;
+
 #[derive(Serialize, Deserialize, Clone)]
 #[napi(object)]
 pub struct BridgeConfigServicePermission {
     pub service: Option<String>,
     pub level: String,
     pub targets: Option<Vec<String>>,
+    /**
+     * If true, this entry denies the level (and every level above it) rather than granting it.
+     * Deny entries always take precedence over grants.
+     */
+    pub deny: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 #[napi(object)]
 pub struct BridgeConfigActorPermission {
     pub actor: String,
+    /**
+     * Named roles whose services are granted to this actor, in addition to `services`.
+     */
+    pub roles: Option<Vec<String>>,
+    /**
+     * For room actors, only match members with at least this power level in the room.
+     */
+    pub power_level: Option<i64>,
+    /**
+     * The time from which this entry applies, as an RFC 3339 timestamp or a `YYYY-MM-DD` date (UTC).
+     */
+    pub not_before: Option<String>,
+    /**
+     * The time from which this entry no longer applies, in the same format as `notBefore`.
+     */
+    pub expires: Option<String>,
+    /**
+     * For room actors that are spaces, also match members of the space's child rooms.
+     */
+    pub include_space_children: Option<bool>,
     pub services: Vec<BridgeConfigServicePermission>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+#[napi(object)]
+pub struct BridgeConfigPermissionRole {
+    pub services: Option<Vec<BridgeConfigServicePermission>>,
+    /**
+     * Other roles whose services are included in this role.
+     */
+    pub roles: Option<Vec<String>>,
+}
+
+enum RoleError {
+    Unknown(String),
+    /**
+     * The chain of roles that lead back to a role already being expanded.
+     */
+    Cycle(Vec<String>),
+}
+
+impl std::fmt::Display for RoleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoleError::Unknown(name) => write!(f, "role '{}' is not defined", name),
+            RoleError::Cycle(path) => write!(f, "roles form a cycle: {}", path.join(" -> ")),
+        }
+    }
+}
+
+/**
+ * Expand role names into the services they grant, including those of nested roles.
+ */
+fn expand_roles(
+    names: &[String],
+    roles: &HashMap<String, BridgeConfigPermissionRole>,
+    path: &mut Vec<String>,
+    services: &mut Vec<BridgeConfigServicePermission>,
+) -> Result<(), RoleError> {
+    for name in names {
+        if path.contains(name) {
+            let mut cycle = path.clone();
+            cycle.push(name.clone());
+            return Err(RoleError::Cycle(cycle));
+        }
+        let role = roles
+            .get(name)
+            .ok_or_else(|| RoleError::Unknown(name.clone()))?;
+        path.push(name.clone());
+        expand_roles(
+            role.roles.as_deref().unwrap_or_default(),
+            roles,
+            path,
+            services,
+        )?;
+        services.extend(role.services.iter().flatten().cloned());
+        path.pop();
+    }
+    Ok(())
+}
+
+/**
+ * Parse a `notBefore` or `expires` value, which may be an RFC 3339 timestamp or a date.
+ */
+fn parse_grant_time(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|t| t.and_utc())
+        })
+}
+
+/**
+ * The period during which an actor entry applies.
+ */
+#[derive(Clone, Copy)]
+struct Validity {
+    not_before: Option<DateTime<Utc>>,
+    expires: Option<DateTime<Utc>>,
+}
+
+impl Validity {
+    /**
+     * Parse the validity of an entry, or return the invalid field and a description of the problem.
+     */
+    fn parse(
+        entry: &BridgeConfigActorPermission,
+    ) -> Result<Option<Validity>, (&'static str, String)> {
+        let parse = |field: &'static str, value: &Option<String>| match value {
+            Some(value) => parse_grant_time(value).map(Some).ok_or_else(|| {
+                (
+                    field,
+                    format!(
+                        "{} '{}' is not an RFC 3339 timestamp or YYYY-MM-DD date",
+                        field, value
+                    ),
+                )
+            }),
+            None => Ok(None),
+        };
+        let validity = Validity {
+            not_before: parse("notBefore", &entry.not_before)?,
+            expires: parse("expires", &entry.expires)?,
+        };
+        Ok((validity.not_before.is_some() || validity.expires.is_some()).then_some(validity))
+    }
+
+    fn contains(&self, now: DateTime<Utc>) -> bool {
+        self.not_before.is_none_or(|t| now >= t) && self.expires.is_none_or(|t| now < t)
+    }
+}
+
+/**
+ * Services that hookshot checks permissions for, including the legacy "webhooks" name.
+ */
+const KNOWN_SERVICES: [&str; 11] = [
+    "*",
+    "challengehound",
+    "feeds",
+    "figma",
+    "generic",
+    "genericOutbound",
+    "github",
+    "gitlab",
+    "jira",
+    "openproject",
+    "webhooks",
+];
+
 #[napi]
 pub fn permission_level_to_int(level: String) -> napi::Result<u32> {
     match level.as_str() {
@@ -30,64 +189,862 @@ pub fn permission_level_to_int(level: String) -> napi::Result<u32> {
     }
 }
 
+/**
+ * Whether an actor is a glob pattern, such as `@*-bot:example.com` or `*.example.com`.
+ */
+fn is_actor_glob(actor: &str) -> bool {
+    actor != "*" && !actor.starts_with('!') && actor.contains(['*', '?'])
+}
+
+/**
+ * Compile a glob pattern into an anchored regex, where `*` matches any number of
+ * characters and `?` matches exactly one.
+ */
+fn compile_glob(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut expr = String::with_capacity(pattern.len() + 8);
+    expr.push('^');
+    let mut literal = [0u8; 4];
+    for c in pattern.chars() {
+        match c {
+            '*' => expr.push_str(".*"),
+            '?' => expr.push('.'),
+            _ => expr.push_str(&regex::escape(c.encode_utf8(&mut literal))),
+        }
+    }
+    expr.push('$');
+    Regex::new(&expr)
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+#[napi(object)]
+pub struct PermissionExplanation {
+    pub allowed: bool,
+    /**
+     * A human readable explanation of the decision.
+     */
+    pub reason: String,
+    /**
+     * The index of the actor entry in the config that decided the outcome, or that came closest
+     * to granting the permission.
+     */
+    pub entry_index: Option<u32>,
+    pub actor: Option<String>,
+    pub service: Option<String>,
+    pub level: Option<String>,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+#[napi(object)]
+pub struct ExpiringPermission {
+    /**
+     * The index of the actor entry in the config.
+     */
+    pub entry_index: u32,
+    pub actor: String,
+    /**
+     * When the entry expires, as an RFC 3339 timestamp.
+     */
+    pub expires: String,
+    /**
+     * Whether the entry has already expired, and can be removed from the config.
+     */
+    pub expired: bool,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+#[napi(object)]
+pub struct PermissionConfigUpdate {
+    /**
+     * Rooms that are now referenced by the config, and whose membership must be fetched.
+     */
+    pub added_rooms: Vec<String>,
+    /**
+     * Rooms that are no longer referenced by the config, and no longer need to be tracked.
+     */
+    pub removed_rooms: Vec<String>,
+    /**
+     * Rooms that were already tracked, but are now referenced with a `powerLevel` and
+     * whose power levels must be fetched.
+     */
+    pub added_power_level_rooms: Vec<String>,
+    /**
+     * Spaces that are now referenced with `includeSpaceChildren`, and whose child rooms
+     * must be fetched.
+     */
+    pub added_spaces: Vec<String>,
+}
+
+/**
+ * Whether an entry's actor matches a user.
+ */
+enum ActorMatch {
+    Matched,
+    NotMatched,
+    /**
+     * The actor covers the user, but the entry doesn't currently apply to them. Holds a
+     * description of why.
+     */
+    Inactive(String),
+}
+
+/**
+ * The outcome of a permission check. Entries are identified by their index in the config,
+ * and the index of the service entry within them.
+ */
+enum Decision {
+    Granted(usize, usize),
+    Denied(usize, usize),
+    NotGranted,
+}
+
+#[napi(string_enum)]
+#[derive(PartialEq, Eq)]
+pub enum PermissionDiagnosticSeverity {
+    /**
+     * The entry is invalid, and will cause errors when checking permissions.
+     */
+    Error,
+    /**
+     * The entry is valid but probably does not do what was intended.
+     */
+    Warning,
+}
+
+#[napi(object)]
+pub struct PermissionDiagnostic {
+    pub severity: PermissionDiagnosticSeverity,
+    /**
+     * The index of the actor entry in the config, if the problem is in an actor entry.
+     */
+    pub entry_index: Option<u32>,
+    /**
+     * The name of the role, if the problem is in a role definition.
+     */
+    pub role: Option<String>,
+    /**
+     * The path to the problematic field within the entry or role, e.g. `services[0].level`.
+     */
+    pub field: String,
+    pub message: String,
+}
+
+/**
+ * Whether a string looks like a server name, optionally with a port. Globs are allowed in the hostname.
+ */
+fn is_valid_server_name(server_name: &str) -> bool {
+    let is_port = |port: &str| !port.is_empty() && port.chars().all(|c| c.is_ascii_digit());
+    let (host, port) = match server_name.strip_prefix('[') {
+        Some(ipv6) => {
+            return match ipv6.split_once(']') {
+                Some((address, port)) => {
+                    !address.is_empty()
+                        && address
+                            .chars()
+                            .all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.')
+                        && (port.is_empty() || port.strip_prefix(':').is_some_and(is_port))
+                }
+                None => false,
+            };
+        }
+        None => match server_name.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (server_name, None),
+        },
+    };
+    !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '*' | '?'))
+        && port.is_none_or(is_port)
+}
+
+/**
+ * Describe why an actor is malformed, if it is.
+ */
+fn validate_actor(actor: &str) -> Option<String> {
+    if actor == "*" {
+        return None;
+    }
+    if actor.is_empty() {
+        return Some("actor must not be empty".to_string());
+    }
+    let valid = if let Some(user_id) = actor.strip_prefix('@') {
+        user_id
+            .split_once(':')
+            .is_some_and(|(localpart, server_name)| {
+                !localpart.is_empty() && is_valid_server_name(server_name)
+            })
+    } else if let Some(room_id) = actor.strip_prefix('!') {
+        !room_id.is_empty() && !room_id.contains(['*', '?'])
+    } else {
+        is_valid_server_name(actor)
+    };
+    if !valid {
+        return Some(format!(
+            "actor '{}' is not a user ID, room ID, server name or '*'",
+            actor
+        ));
+    }
+    if is_actor_glob(actor) {
+        if let Err(err) = compile_glob(actor) {
+            return Some(format!("actor '{}' is not a valid pattern: {}", actor, err));
+        }
+    }
+    None
+}
+
+/**
+ * Check a list of service entries for mistakes.
+ */
+fn validate_services(
+    services: &[BridgeConfigServicePermission],
+    report: &mut impl FnMut(PermissionDiagnosticSeverity, String, String),
+) {
+    for (service_index, service) in services.iter().enumerate() {
+        let field = |name: &str| format!("services[{}].{}", service_index, name);
+        if let Some(name) = &service.service {
+            if !KNOWN_SERVICES.contains(&name.as_str()) {
+                report(
+                    PermissionDiagnosticSeverity::Warning,
+                    field("service"),
+                    format!(
+                        "'{}' is not a known service, expected one of: {}",
+                        name,
+                        KNOWN_SERVICES.join(", ")
+                    ),
+                );
+            }
+        }
+        if permission_level_to_int(service.level.clone()).is_err() {
+            report(
+                PermissionDiagnosticSeverity::Error,
+                field("level"),
+                format!(
+                    "'{}' is not a valid level, expected one of: commands, login, notifications, manageConnections, admin",
+                    service.level
+                ),
+            );
+        }
+        match &service.targets {
+            Some(targets) if targets.is_empty() => report(
+                PermissionDiagnosticSeverity::Warning,
+                field("targets"),
+                "targets is empty, so this entry never applies".to_string(),
+            ),
+            Some(targets) => {
+                for (target_index, target) in targets.iter().enumerate() {
+                    let message = if target.is_empty() {
+                        Some("target must not be empty".to_string())
+                    } else {
+                        compile_glob(target).err().map(|err| {
+                            format!("target '{}' is not a valid pattern: {}", target, err)
+                        })
+                    };
+                    if let Some(message) = message {
+                        report(
+                            PermissionDiagnosticSeverity::Error,
+                            format!("services[{}].targets[{}]", service_index, target_index),
+                            message,
+                        );
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/**
+ * Report any references to roles which are not defined.
+ */
+fn validate_role_references(
+    names: &[String],
+    roles: &HashMap<String, BridgeConfigPermissionRole>,
+    report: &mut impl FnMut(PermissionDiagnosticSeverity, String, String),
+) {
+    for (index, name) in names.iter().enumerate() {
+        if !roles.contains_key(name) {
+            report(
+                PermissionDiagnosticSeverity::Error,
+                format!("roles[{}]", index),
+                RoleError::Unknown(name.clone()).to_string(),
+            );
+        }
+    }
+}
+
+/**
+ * Check a permission config for mistakes, returning a diagnostic for each problem found.
+ */
 #[napi]
-struct BridgePermissions {
+pub fn validate_permission_config(
     config: Vec<BridgeConfigActorPermission>,
+    roles: Option<HashMap<String, BridgeConfigPermissionRole>>,
+) -> Vec<PermissionDiagnostic> {
+    let roles = roles.unwrap_or_default();
+    let mut diagnostics = Vec::new();
+    let mut role_names: Vec<&String> = roles.keys().collect();
+    role_names.sort();
+    for name in role_names {
+        let role = &roles[name];
+        let mut report = |severity, field: String, message: String| {
+            diagnostics.push(PermissionDiagnostic {
+                severity,
+                entry_index: None,
+                role: Some(name.clone()),
+                field,
+                message,
+            })
+        };
+        validate_services(role.services.as_deref().unwrap_or_default(), &mut report);
+        validate_role_references(
+            role.roles.as_deref().unwrap_or_default(),
+            &roles,
+            &mut report,
+        );
+        // Only report a cycle on the roles that are part of it.
+        if let Err(RoleError::Cycle(path)) =
+            expand_roles(std::slice::from_ref(name), &roles, &mut vec![], &mut vec![])
+        {
+            if path.last() == Some(name) {
+                report(
+                    PermissionDiagnosticSeverity::Error,
+                    "roles".to_string(),
+                    RoleError::Cycle(path).to_string(),
+                );
+            }
+        }
+    }
+    for (entry_index, entry) in config.iter().enumerate() {
+        let mut report = |severity, field: String, message: String| {
+            diagnostics.push(PermissionDiagnostic {
+                severity,
+                entry_index: Some(entry_index as u32),
+                role: None,
+                field,
+                message,
+            })
+        };
+        if let Some(message) = validate_actor(&entry.actor) {
+            report(
+                PermissionDiagnosticSeverity::Error,
+                "actor".to_string(),
+                message,
+            );
+        }
+        if entry.power_level.is_some() && !entry.actor.starts_with('!') {
+            report(
+                PermissionDiagnosticSeverity::Error,
+                "powerLevel".to_string(),
+                "powerLevel can only be used with room actors".to_string(),
+            );
+        }
+        if entry.include_space_children.is_some() && !entry.actor.starts_with('!') {
+            report(
+                PermissionDiagnosticSeverity::Error,
+                "includeSpaceChildren".to_string(),
+                "includeSpaceChildren can only be used with room actors".to_string(),
+            );
+        } else if entry.include_space_children == Some(true) && entry.power_level.is_some() {
+            report(
+                PermissionDiagnosticSeverity::Error,
+                "includeSpaceChildren".to_string(),
+                "includeSpaceChildren cannot be combined with powerLevel".to_string(),
+            );
+        }
+        match Validity::parse(entry) {
+            Ok(Some(Validity {
+                not_before: Some(not_before),
+                expires: Some(expires),
+            })) if not_before >= expires => report(
+                PermissionDiagnosticSeverity::Warning,
+                "expires".to_string(),
+                "expires is not after notBefore, so this entry never applies".to_string(),
+            ),
+            Ok(_) => {}
+            Err((field, message)) => report(
+                PermissionDiagnosticSeverity::Error,
+                field.to_string(),
+                message,
+            ),
+        }
+        let entry_roles = entry.roles.as_deref().unwrap_or_default();
+        if entry.services.is_empty() && entry_roles.is_empty() {
+            report(
+                PermissionDiagnosticSeverity::Warning,
+                "services".to_string(),
+                "entry has no services or roles, so grants nothing".to_string(),
+            );
+        }
+        validate_services(&entry.services, &mut report);
+        validate_role_references(entry_roles, &roles, &mut report);
+    }
+    diagnostics
+}
+
+/**
+ * The user power levels from a room's `m.room.power_levels` event.
+ */
+#[derive(Default)]
+struct RoomPowerLevels {
+    users: HashMap<String, i64>,
+    users_default: i64,
+}
+
+/**
+ * Entries grouped by the kind of actor, so that checks only consider entries which could
+ * match a user. Each list is in config order.
+ */
+#[derive(Default)]
+struct ActorIndex {
+    by_mxid: HashMap<String, Vec<usize>>,
+    by_domain: HashMap<String, Vec<usize>>,
+    /**
+     * Room actors, which match depending on the membership cache.
+     */
+    rooms: Vec<usize>,
+    /**
+     * Glob and `*` actors, which may match any user.
+     */
+    wildcard: Vec<usize>,
+}
+
+impl ActorIndex {
+    fn new(config: &[BridgeConfigActorPermission]) -> Self {
+        let mut index = ActorIndex::default();
+        for (entry_index, entry) in config.iter().enumerate() {
+            let actor = &entry.actor;
+            if actor.starts_with('!') {
+                index.rooms.push(entry_index);
+            } else if actor == "*" || is_actor_glob(actor) {
+                index.wildcard.push(entry_index);
+            } else if actor.starts_with('@') {
+                index
+                    .by_mxid
+                    .entry(actor.clone())
+                    .or_default()
+                    .push(entry_index);
+            } else {
+                index
+                    .by_domain
+                    .entry(actor.clone())
+                    .or_default()
+                    .push(entry_index);
+            }
+        }
+        index
+    }
+
+    /**
+     * The entries which could match a user, in config order.
+     */
+    fn candidates(&self, domain: &str, mxid: &str) -> Vec<usize> {
+        let mut candidates: Vec<usize> = self
+            .by_mxid
+            .get(mxid)
+            .into_iter()
+            .flatten()
+            .chain(self.by_domain.get(domain).into_iter().flatten())
+            .chain(self.rooms.iter())
+            .chain(self.wildcard.iter())
+            .copied()
+            .collect();
+        candidates.sort_unstable();
+        candidates
+    }
+}
+
+#[napi]
+pub struct BridgePermissions {
+    config: Vec<BridgeConfigActorPermission>,
+    index: ActorIndex,
+    /**
+     * The parsed level of each service entry in `config`, or `None` if it is invalid.
+     */
+    levels: Vec<Vec<Option<u32>>>,
     room_membership: HashMap<String, HashSet<String>>,
+    /**
+     * The validity of each entry in `config`, if it is time bounded.
+     */
+    validity: Vec<Option<Validity>>,
+    /**
+     * Power levels for rooms referenced by actors with a `powerLevel`.
+     */
+    room_power_levels: HashMap<String, RoomPowerLevels>,
+    /**
+     * Child rooms of spaces referenced by actors with `includeSpaceChildren`. The membership
+     * of each child is tracked in `room_membership`.
+     */
+    space_children: HashMap<String, HashSet<String>>,
+    /**
+     * Rules from each room's permission overrides state event. These are checked in addition
+     * to the config, so can only narrow what is allowed in the room.
+     */
+    room_overrides: HashMap<String, BridgePermissions>,
+    /**
+     * Compiled glob actors, keyed by the actor string.
+     */
+    actor_globs: HashMap<String, Regex>,
+    /**
+     * Compiled glob targets, keyed by the target pattern.
+     */
+    target_globs: HashMap<String, Regex>,
 }
 
 #[napi]
 impl BridgePermissions {
+    /**
+     * @param config The actor entries, in order.
+     * @param roles Named roles which actor entries may reference.
+     */
     #[napi(constructor)]
-    pub fn new(config: Vec<BridgeConfigActorPermission>) -> Self {
+    pub fn new(
+        config: Vec<BridgeConfigActorPermission>,
+        roles: Option<HashMap<String, BridgeConfigPermissionRole>>,
+    ) -> napi::Result<Self> {
+        let roles = roles.unwrap_or_default();
         let mut room_membership = HashMap::new();
+        let mut room_power_levels = HashMap::new();
+        let mut space_children = HashMap::new();
+        let mut actor_globs = HashMap::new();
+        let mut target_globs = HashMap::new();
         let mut new_config = config.clone();
+        let mut validity = Vec::with_capacity(new_config.len());
         for entry in new_config.iter_mut() {
+            validity.push(Validity::parse(entry).map_err(|(_, err)| {
+                napi::Error::new(
+                    napi::Status::InvalidArg,
+                    format!("Invalid times for actor '{}': {}", entry.actor, err),
+                )
+            })?);
+            if let Some(entry_roles) = &entry.roles {
+                expand_roles(entry_roles, &roles, &mut vec![], &mut entry.services).map_err(
+                    |err| {
+                        napi::Error::new(
+                            napi::Status::InvalidArg,
+                            format!("Invalid roles for actor '{}': {}", entry.actor, err),
+                        )
+                    },
+                )?;
+            }
             for perm in entry.services.iter_mut() {
                 // This is fixing a legacy mistake in that we used "webhooks"
                 // and "generic" interchangeably.
                 if perm.service.clone().is_some_and(|s| s == "webhooks") {
                     perm.service = Some("generic".to_string())
                 }
+                for target in perm.targets.iter().flatten() {
+                    if target.contains(['*', '?']) && !target_globs.contains_key(target) {
+                        let glob = compile_glob(target).map_err(|err| {
+                            napi::Error::new(
+                                napi::Status::InvalidArg,
+                                format!("target '{}' is not a valid pattern: {}", target, err),
+                            )
+                        })?;
+                        target_globs.insert(target.clone(), glob);
+                    }
+                }
             }
             if entry.actor.starts_with('!') {
                 room_membership.insert(entry.actor.clone(), HashSet::new());
+                if entry.power_level.is_some() {
+                    room_power_levels.insert(entry.actor.clone(), RoomPowerLevels::default());
+                }
+                if entry.include_space_children == Some(true) {
+                    if entry.power_level.is_some() {
+                        return Err(napi::Error::new(
+                            napi::Status::InvalidArg,
+                            format!(
+                                "Actor '{}' cannot combine includeSpaceChildren with powerLevel",
+                                entry.actor
+                            ),
+                        ));
+                    }
+                    space_children.insert(entry.actor.clone(), HashSet::new());
+                }
+            } else if is_actor_glob(&entry.actor) && !actor_globs.contains_key(&entry.actor) {
+                let glob = compile_glob(&entry.actor).map_err(|err| {
+                    napi::Error::new(
+                        napi::Status::InvalidArg,
+                        format!("actor '{}' is not a valid pattern: {}", entry.actor, err),
+                    )
+                })?;
+                actor_globs.insert(entry.actor.clone(), glob);
             }
         }
 
-        BridgePermissions {
+        let levels = new_config
+            .iter()
+            .map(|entry| {
+                entry
+                    .services
+                    .iter()
+                    .map(|s| permission_level_to_int(s.level.clone()).ok())
+                    .collect()
+            })
+            .collect();
+        Ok(BridgePermissions {
+            index: ActorIndex::new(&new_config),
+            levels,
             config: new_config,
             room_membership,
+            validity,
+            room_power_levels,
+            space_children,
+            room_overrides: HashMap::new(),
+            actor_globs,
+            target_globs,
+        })
+    }
+
+    fn match_actor(&self, entry_index: usize, domain: &String, mxid: &String) -> bool {
+        matches!(
+            self.check_actor(entry_index, domain, mxid),
+            ActorMatch::Matched
+        )
+    }
+
+    /**
+     * Check whether an entry's actor matches a user, and if so whether the entry applies to
+     * them right now.
+     */
+    fn check_actor(&self, entry_index: usize, domain: &String, mxid: &String) -> ActorMatch {
+        let actor_permission = &self.config[entry_index];
+        let actor_match = if actor_permission.actor.starts_with('!') {
+            self.check_room_actor(actor_permission, mxid)
+        } else if let Some(glob) = self.actor_globs.get(&actor_permission.actor) {
+            // Patterns starting with @ match user IDs, anything else matches the domain.
+            let matched = if actor_permission.actor.starts_with('@') {
+                glob.is_match(mxid)
+            } else {
+                glob.is_match(domain)
+            };
+            if matched {
+                ActorMatch::Matched
+            } else {
+                ActorMatch::NotMatched
+            }
+        } else if actor_permission.actor.eq(domain)
+            || actor_permission.actor.eq(mxid)
+            || actor_permission.actor == "*"
+        {
+            ActorMatch::Matched
+        } else {
+            ActorMatch::NotMatched
+        };
+        let (ActorMatch::Matched, Some(validity)) = (&actor_match, self.validity[entry_index])
+        else {
+            return actor_match;
+        };
+        let now = Utc::now();
+        if validity.contains(now) {
+            return actor_match;
         }
+        ActorMatch::Inactive(match validity.not_before.filter(|t| now < *t) {
+            Some(not_before) => format!(
+                "Actor '{}' only applies from {}",
+                actor_permission.actor,
+                not_before.to_rfc3339()
+            ),
+            None => format!(
+                "Actor '{}' expired at {}",
+                actor_permission.actor,
+                validity.expires.map(|t| t.to_rfc3339()).unwrap_or_default()
+            ),
+        })
     }
 
-    fn match_actor(
+    fn check_room_actor(
         &self,
         actor_permission: &BridgeConfigActorPermission,
-        domain: &String,
         mxid: &String,
-    ) -> bool {
-        if actor_permission.actor.starts_with('!') {
-            match self.room_membership.get(&actor_permission.actor) {
-                Some(set) => {
-                    return set.contains(mxid);
-                }
-                None => {
-                    // No cached data stored...odd.
-                    return false;
+    ) -> ActorMatch {
+        if actor_permission.include_space_children == Some(true) {
+            let in_child_room = self
+                .space_children
+                .get(&actor_permission.actor)
+                .is_some_and(|children| {
+                    children.iter().any(|child| {
+                        self.room_membership
+                            .get(child)
+                            .is_some_and(|set| set.contains(mxid))
+                    })
+                });
+            if in_child_room {
+                return ActorMatch::Matched;
+            }
+        }
+        // No cached data stored...odd.
+        let Some(set) = self.room_membership.get(&actor_permission.actor) else {
+            return ActorMatch::NotMatched;
+        };
+        if !set.contains(mxid) {
+            return ActorMatch::NotMatched;
+        }
+        let Some(min_level) = actor_permission.power_level else {
+            return ActorMatch::Matched;
+        };
+        let Some(levels) = self.room_power_levels.get(&actor_permission.actor) else {
+            return ActorMatch::NotMatched;
+        };
+        let level = levels
+            .users
+            .get(mxid)
+            .copied()
+            .unwrap_or(levels.users_default);
+        if level >= min_level {
+            ActorMatch::Matched
+        } else {
+            ActorMatch::Inactive(format!(
+                "Actor '{}' requires power level {}, but '{}' has {}",
+                actor_permission.actor, min_level, mxid, level
+            ))
+        }
+    }
+
+    /**
+     * Replace the permission rules, keeping the cached membership of any rooms
+     * that are still referenced. Returns the rooms and spaces which were added and
+     * removed, so that only those need to be synced.
+     */
+    #[napi]
+    pub fn update_config(
+        &mut self,
+        config: Vec<BridgeConfigActorPermission>,
+        roles: Option<HashMap<String, BridgeConfigPermissionRole>>,
+    ) -> napi::Result<PermissionConfigUpdate> {
+        let mut updated = BridgePermissions::new(config, roles)?;
+        let mut added_spaces = Vec::new();
+        for (space_id, children) in updated.space_children.iter_mut() {
+            match self.space_children.remove(space_id) {
+                Some(existing) => *children = existing,
+                None => added_spaces.push(space_id.clone()),
+            }
+        }
+        for child in updated.space_children.values().flatten() {
+            updated
+                .room_membership
+                .entry(child.clone())
+                .or_insert_with(HashSet::new);
+        }
+        let mut added_rooms = Vec::new();
+        for (room_id, members) in updated.room_membership.iter_mut() {
+            match self.room_membership.remove(room_id) {
+                Some(existing) => *members = existing,
+                None => added_rooms.push(room_id.clone()),
+            }
+        }
+        let mut added_power_level_rooms = Vec::new();
+        for (room_id, levels) in updated.room_power_levels.iter_mut() {
+            match self.room_power_levels.remove(room_id) {
+                Some(existing) => *levels = existing,
+                // New rooms have their power levels fetched along with their membership.
+                None if !added_rooms.contains(room_id) => {
+                    added_power_level_rooms.push(room_id.clone())
                 }
+                None => {}
             }
         }
-        actor_permission.actor.eq(domain)
-            || actor_permission.actor.eq(mxid)
-            || actor_permission.actor == "*"
+        updated.room_overrides = std::mem::take(&mut self.room_overrides);
+        let removed_rooms = self.room_membership.keys().cloned().collect();
+        *self = updated;
+        Ok(PermissionConfigUpdate {
+            added_rooms,
+            removed_rooms,
+            added_power_level_rooms,
+            added_spaces,
+        })
     }
 
+    /**
+     * List entries which expire within the given number of milliseconds, including
+     * those which have already expired, ordered by when they expire.
+     */
+    #[napi]
+    pub fn get_expiring_permissions(&self, within_ms: f64) -> Vec<ExpiringPermission> {
+        let now = Utc::now();
+        // Windows too large to represent have no upper limit.
+        let horizon = chrono::Duration::try_milliseconds(within_ms.max(0f64) as i64)
+            .and_then(|within| now.checked_add_signed(within))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let mut expiring: Vec<(DateTime<Utc>, usize)> = self
+            .validity
+            .iter()
+            .enumerate()
+            .filter_map(|(entry_index, validity)| {
+                validity
+                    .and_then(|v| v.expires)
+                    .filter(|expires| *expires <= horizon)
+                    .map(|expires| (expires, entry_index))
+            })
+            .collect();
+        expiring.sort();
+        expiring
+            .into_iter()
+            .map(|(expires, entry_index)| ExpiringPermission {
+                entry_index: entry_index as u32,
+                actor: self.config[entry_index].actor.clone(),
+                expires: expires.to_rfc3339(),
+                expired: expires <= now,
+            })
+            .collect()
+    }
+
+    /**
+     * Get all rooms whose membership must be tracked, including the child rooms of spaces.
+     */
     #[napi]
     pub fn get_interested_rooms(&self) -> Vec<String> {
         self.room_membership.keys().cloned().collect()
     }
 
+    /**
+     * Get the spaces whose child rooms are used to match actors.
+     */
+    #[napi]
+    pub fn get_interested_spaces(&self) -> Vec<String> {
+        self.space_children.keys().cloned().collect()
+    }
+
+    /**
+     * Replace the child rooms of a space. Returns the rooms which must now be tracked,
+     * and those which no longer need to be.
+     */
+    #[napi]
+    pub fn set_space_children(
+        &mut self,
+        space_id: String,
+        children: Vec<String>,
+    ) -> PermissionConfigUpdate {
+        let mut update = PermissionConfigUpdate {
+            added_rooms: Vec::new(),
+            removed_rooms: Vec::new(),
+            added_power_level_rooms: Vec::new(),
+            added_spaces: Vec::new(),
+        };
+        let children: HashSet<String> = children.into_iter().collect();
+        let Some(existing) = self.space_children.get_mut(&space_id) else {
+            return update;
+        };
+        let previous = std::mem::replace(existing, children.clone());
+        for child in children.difference(&previous) {
+            if !self.room_membership.contains_key(child) {
+                self.room_membership.insert(child.clone(), HashSet::new());
+                update.added_rooms.push(child.clone());
+            }
+        }
+        for child in previous.difference(&children) {
+            // Keep tracking rooms which are still referenced elsewhere.
+            let referenced = self.config.iter().any(|entry| &entry.actor == child)
+                || self.space_children.values().any(|c| c.contains(child));
+            if !referenced {
+                self.room_membership.remove(child);
+                update.removed_rooms.push(child.clone());
+            }
+        }
+        update
+    }
+
     #[napi]
     pub fn add_member_to_cache(&mut self, room_id: String, mxid: String) {
         if let Some(set) = self.room_membership.get_mut(&room_id) {
@@ -102,35 +1059,451 @@ impl BridgePermissions {
         }
     }
 
+    /**
+     * Update the cached power levels for a room from the content of its `m.room.power_levels` event.
+     */
     #[napi]
-    pub fn check_action(
+    pub fn update_power_levels(
+        &mut self,
+        room_id: String,
+        content: serde_json::Value,
+    ) -> napi::Result<()> {
+        let Some(levels) = self.room_power_levels.get_mut(&room_id) else {
+            return Ok(());
+        };
+        let content: RoomPowerLevelsEventContent =
+            serde_json::from_value(content).map_err(|err| {
+                napi::Error::new(
+                    napi::Status::InvalidArg,
+                    format!("Invalid power levels for {}: {}", room_id, err),
+                )
+            })?;
+        *levels = RoomPowerLevels {
+            users: content
+                .users
+                .into_iter()
+                .map(|(user_id, level)| (user_id.to_string(), level.into()))
+                .collect(),
+            users_default: content.users_default.into(),
+        };
+        Ok(())
+    }
+
+    /**
+     * The level of a service entry, which fails the check if it isn't valid.
+     */
+    fn level(&self, entry_index: usize, service_index: usize) -> napi::Result<u32> {
+        self.levels[entry_index][service_index].ok_or_else(|| {
+            napi::Error::new(
+                napi::Status::InvalidArg,
+                "provided level wasn't valid".to_string(),
+            )
+        })
+    }
+
+    /**
+     * Whether a service entry applies to a target. Entries without `targets` apply to every target.
+     * Entries with `targets` apply when any of them is equal to, or a glob matching, the target.
+     */
+    fn match_target(&self, actor_service: &BridgeConfigServicePermission, target: &str) -> bool {
+        let Some(targets) = &actor_service.targets else {
+            return true;
+        };
+        targets
+            .iter()
+            .any(|pattern| match self.target_globs.get(pattern) {
+                Some(glob) => glob.is_match(target),
+                None => pattern == target,
+            })
+    }
+
+    /**
+     * Whether a service entry applies to a check on an optional target. Checks without a
+     * target only use entries that aren't scoped to `targets`.
+     */
+    fn applies_to_target(
+        &self,
+        actor_service: &BridgeConfigServicePermission,
+        target: Option<&str>,
+    ) -> bool {
+        match target {
+            Some(target) => self.match_target(actor_service, target),
+            None => actor_service.targets.is_none(),
+        }
+    }
+
+    /**
+     * Find a matching deny entry that forbids the given permission level. A `service` of `None`
+     * means the check is for any service, so only deny entries covering all services apply.
+     * Likewise, deny entries scoped to `targets` only apply when checking a matching target.
+     */
+    fn find_deny(
+        &self,
+        candidates: &[usize],
+        domain: &String,
+        mxid: &String,
+        service: Option<&str>,
+        target: Option<&str>,
+        permission_int: u32,
+    ) -> napi::Result<Option<(usize, usize)>> {
+        for &entry_index in candidates {
+            if !self.match_actor(entry_index, domain, mxid) {
+                continue;
+            }
+            let actor_permission = &self.config[entry_index];
+            for (service_index, actor_service) in actor_permission.services.iter().enumerate() {
+                if actor_service.deny != Some(true) {
+                    continue;
+                }
+                let applies = match (actor_service.service.as_deref(), service) {
+                    (None | Some("*"), _) => true,
+                    (Some(denied), Some(service)) => denied == service,
+                    (Some(_), None) => false,
+                };
+                let applies = applies && self.applies_to_target(actor_service, target);
+                // A deny entry forbids its level and every level above it.
+                if applies && self.level(entry_index, service_index)? <= permission_int {
+                    return Ok(Some((entry_index, service_index)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn decide(
+        &self,
+        domain: &String,
+        mxid: &String,
+        service: &String,
+        permission_int: u32,
+        target: Option<&str>,
+    ) -> napi::Result<Decision> {
+        let candidates = self.index.candidates(domain, mxid);
+        if let Some((entry_index, service_index)) = self.find_deny(
+            &candidates,
+            domain,
+            mxid,
+            Some(service),
+            target,
+            permission_int,
+        )? {
+            return Ok(Decision::Denied(entry_index, service_index));
+        }
+        for entry_index in candidates {
+            // Room_id
+            if !self.match_actor(entry_index, domain, mxid) {
+                continue;
+            }
+            if let Some(service_index) =
+                self.find_grant(entry_index, service, permission_int, target)?
+            {
+                return Ok(Decision::Granted(entry_index, service_index));
+            }
+        }
+        Ok(Decision::NotGranted)
+    }
+
+    /**
+     * Find the service entry of an entry that grants the given permission level, regardless of
+     * whether the entry's actor matches.
+     */
+    fn find_grant(
+        &self,
+        entry_index: usize,
+        service: &String,
+        permission_int: u32,
+        target: Option<&str>,
+    ) -> napi::Result<Option<usize>> {
+        let actor_permission = &self.config[entry_index];
+        for (service_index, actor_service) in actor_permission.services.iter().enumerate() {
+            if actor_service.deny == Some(true) {
+                continue;
+            }
+            if let Some(actor_service_service) = &actor_service.service {
+                if actor_service_service != service && actor_service_service != "*" {
+                    continue;
+                }
+            }
+            if !self.applies_to_target(actor_service, target) {
+                continue;
+            }
+            if self.level(entry_index, service_index)? >= permission_int {
+                return Ok(Some(service_index));
+            }
+        }
+        Ok(None)
+    }
+
+    fn check_action_inner(
         &self,
         mxid: String,
         service: String,
         permission: String,
+        target: Option<&str>,
     ) -> napi::Result<bool> {
         let permission_int = permission_level_to_int(permission)?;
         let domain: String = match mxid.split_once(':') {
             Some((.., d)) => d.to_string(),
             None => return Ok(false),
         };
-        for actor_permission in self.config.iter() {
-            // Room_id
-            if !self.match_actor(actor_permission, &domain, &mxid) {
-                continue;
+        Ok(matches!(
+            self.decide(&domain, &mxid, &service, permission_int, target)?,
+            Decision::Granted(..)
+        ))
+    }
+
+    /**
+     * Check if a user has a permission level on a service. Grants scoped to `targets`
+     * don't count here, so use `checkActionForTarget` when acting on a specific target.
+     */
+    #[napi]
+    pub fn check_action(
+        &self,
+        mxid: String,
+        service: String,
+        permission: String,
+    ) -> napi::Result<bool> {
+        self.check_action_inner(mxid, service, permission, None)
+    }
+
+    /**
+     * Check if a user has a permission level on a specific target of a service,
+     * such as `github:matrix-org/matrix-hookshot`.
+     */
+    #[napi]
+    pub fn check_action_for_target(
+        &self,
+        mxid: String,
+        service: String,
+        permission: String,
+        target: String,
+    ) -> napi::Result<bool> {
+        self.check_action_inner(mxid, service, permission, Some(&target))
+    }
+
+    fn explanation(
+        &self,
+        allowed: bool,
+        reason: String,
+        entry: Option<(usize, usize)>,
+    ) -> PermissionExplanation {
+        let entry = entry.map(|(entry_index, service_index)| {
+            let actor_permission = &self.config[entry_index];
+            (
+                entry_index,
+                actor_permission,
+                &actor_permission.services[service_index],
+            )
+        });
+        PermissionExplanation {
+            allowed,
+            reason,
+            entry_index: entry.map(|(index, ..)| index as u32),
+            actor: entry.map(|(_, a, _)| a.actor.clone()),
+            service: entry.map(|(.., s)| s.service.clone().unwrap_or("*".to_string())),
+            level: entry.map(|(.., s)| s.level.clone()),
+        }
+    }
+
+    /**
+     * Set the permission overrides for a room, or remove them if `permissions` is not given.
+     * Overrides use the same format as the config, but cannot use room actors.
+     */
+    #[napi]
+    pub fn set_room_overrides(
+        &mut self,
+        room_id: String,
+        permissions: Option<Vec<BridgeConfigActorPermission>>,
+    ) -> napi::Result<()> {
+        let Some(permissions) = permissions else {
+            self.room_overrides.remove(&room_id);
+            return Ok(());
+        };
+        if let Some(entry) = permissions.iter().find(|e| e.actor.starts_with('!')) {
+            return Err(napi::Error::new(
+                napi::Status::InvalidArg,
+                format!(
+                    "Room permission overrides cannot use room actors, found '{}'",
+                    entry.actor
+                ),
+            ));
+        }
+        self.room_overrides
+            .insert(room_id, BridgePermissions::new(permissions, None)?);
+        Ok(())
+    }
+
+    /**
+     * Check if a user has a permission level on a service in a room. This must be allowed by
+     * both the config and the room's permission overrides, if it has any.
+     */
+    #[napi]
+    pub fn check_action_in_room(
+        &self,
+        mxid: String,
+        room_id: String,
+        service: String,
+        permission: String,
+        target: Option<String>,
+    ) -> napi::Result<bool> {
+        let target = target.as_deref();
+        if !self.check_action_inner(mxid.clone(), service.clone(), permission.clone(), target)? {
+            return Ok(false);
+        }
+        match self.room_overrides.get(&room_id) {
+            Some(overrides) => overrides.check_action_inner(mxid, service, permission, target),
+            None => Ok(true),
+        }
+    }
+
+    /**
+     * Explain why a user does or does not have a permission level on a service, and optionally
+     * a specific target. If the permission is not granted, this reports the closest entry that
+     * fell short, including entries that are outside their validity period or that require a
+     * higher power level.
+     */
+    #[napi]
+    pub fn explain_action(
+        &self,
+        mxid: String,
+        service: String,
+        permission: String,
+        target: Option<String>,
+    ) -> napi::Result<PermissionExplanation> {
+        let permission_int = permission_level_to_int(permission.clone())?;
+        let domain: String = match mxid.split_once(':') {
+            Some((.., d)) => d.to_string(),
+            None => {
+                return Ok(self.explanation(
+                    false,
+                    format!("'{}' is not a valid user ID", mxid),
+                    None,
+                ))
+            }
+        };
+        let target = target.as_deref();
+        match self.decide(&domain, &mxid, &service, permission_int, target)? {
+            Decision::Granted(entry_index, service_index) => {
+                let actor_service = &self.config[entry_index].services[service_index];
+                return Ok(self.explanation(
+                    true,
+                    format!(
+                        "Actor '{}' grants '{}' on service '{}'",
+                        self.config[entry_index].actor,
+                        actor_service.level,
+                        actor_service.service.as_deref().unwrap_or("*")
+                    ),
+                    Some((entry_index, service_index)),
+                ));
             }
-            for actor_service in actor_permission.services.iter() {
-                if let Some(actor_service_service) = &actor_service.service {
-                    if actor_service_service != &service && actor_service_service != "*" {
-                        continue;
+            Decision::Denied(entry_index, service_index) => {
+                let actor_service = &self.config[entry_index].services[service_index];
+                return Ok(self.explanation(
+                    false,
+                    format!(
+                        "Actor '{}' denies '{}' and above on service '{}'",
+                        self.config[entry_index].actor,
+                        actor_service.level,
+                        actor_service.service.as_deref().unwrap_or("*")
+                    ),
+                    Some((entry_index, service_index)),
+                ));
+            }
+            Decision::NotGranted => {}
+        }
+
+        // Find the grant that came closest, preferring one that only lacked the level over
+        // one that didn't cover the target, and then the highest level.
+        let mut closest: Option<(bool, u32, usize, usize)> = None;
+        let mut matched_entry = None;
+        // An entry whose actor covers the user but which doesn't currently apply to them,
+        // preferring one that would otherwise have granted the permission.
+        let mut inactive: Option<(Option<usize>, usize, String)> = None;
+        for entry_index in self.index.candidates(&domain, &mxid) {
+            match self.check_actor(entry_index, &domain, &mxid) {
+                ActorMatch::Matched => {}
+                ActorMatch::NotMatched => continue,
+                ActorMatch::Inactive(reason) => {
+                    if inactive.as_ref().is_none_or(|(grant, ..)| grant.is_none()) {
+                        let grant =
+                            self.find_grant(entry_index, &service, permission_int, target)?;
+                        if inactive.is_none() || grant.is_some() {
+                            inactive = Some((grant, entry_index, reason));
+                        }
                     }
+                    continue;
                 }
-                if permission_level_to_int(actor_service.level.clone())? >= permission_int {
-                    return Ok(true);
+            }
+            let actor_permission = &self.config[entry_index];
+            matched_entry.get_or_insert(entry_index);
+            for (service_index, actor_service) in actor_permission.services.iter().enumerate() {
+                if actor_service.deny == Some(true)
+                    || actor_service
+                        .service
+                        .as_ref()
+                        .is_some_and(|s| s != &service && s != "*")
+                {
+                    continue;
+                }
+                let candidate = (
+                    self.applies_to_target(actor_service, target),
+                    self.level(entry_index, service_index)?,
+                    entry_index,
+                    service_index,
+                );
+                if closest.is_none_or(|c| (candidate.0, candidate.1) > (c.0, c.1)) {
+                    closest = Some(candidate);
                 }
             }
         }
-        Ok(false)
+
+        match inactive {
+            Some((Some(service_index), entry_index, reason)) => {
+                return Ok(self.explanation(false, reason, Some((entry_index, service_index))));
+            }
+            Some((None, entry_index, reason)) if closest.is_none() => {
+                return Ok(PermissionExplanation {
+                    entry_index: Some(entry_index as u32),
+                    actor: Some(self.config[entry_index].actor.clone()),
+                    ..self.explanation(false, reason, None)
+                });
+            }
+            _ => {}
+        }
+
+        let reason = match (closest, matched_entry) {
+            (Some((target_matched, _, entry_index, service_index)), _) => {
+                let actor_service = &self.config[entry_index].services[service_index];
+                let reason = if target_matched {
+                    format!(
+                        "Actor '{}' only grants '{}' on service '{}', but '{}' is required",
+                        self.config[entry_index].actor,
+                        actor_service.level,
+                        actor_service.service.as_deref().unwrap_or("*"),
+                        permission
+                    )
+                } else {
+                    format!(
+                        "Actor '{}' grants '{}' on service '{}', but only for targets: {}",
+                        self.config[entry_index].actor,
+                        actor_service.level,
+                        actor_service.service.as_deref().unwrap_or("*"),
+                        actor_service.targets.clone().unwrap_or_default().join(", ")
+                    )
+                };
+                return Ok(self.explanation(false, reason, Some((entry_index, service_index))));
+            }
+            (None, Some(entry_index)) => format!(
+                "Actor '{}' matches, but has no entries for service '{}'",
+                self.config[entry_index].actor, service
+            ),
+            (None, None) => format!("No actors match '{}'", mxid),
+        };
+        Ok(PermissionExplanation {
+            entry_index: matched_entry.map(|i| i as u32),
+            actor: matched_entry.map(|i| self.config[i].actor.clone()),
+            ..self.explanation(false, reason, None)
+        })
     }
 
     #[napi]
@@ -140,12 +1513,25 @@ impl BridgePermissions {
             Some((.., d)) => d.to_string(),
             None => return Ok(false),
         };
-        for actor_permission in self.config.iter() {
-            if !self.match_actor(actor_permission, &domain, &mxid) {
+        let candidates = self.index.candidates(&domain, &mxid);
+        for &entry_index in candidates.iter() {
+            if !self.match_actor(entry_index, &domain, &mxid) {
                 continue;
             }
-            for actor_service in actor_permission.services.iter() {
-                if permission_level_to_int(actor_service.level.clone())? >= permission_int {
+            let actor_permission = &self.config[entry_index];
+            for (service_index, actor_service) in actor_permission.services.iter().enumerate() {
+                if actor_service.deny == Some(true) {
+                    continue;
+                }
+                if self.level(entry_index, service_index)? < permission_int {
+                    continue;
+                }
+                // A grant is only usable if it hasn't been denied for the service it covers.
+                let service = actor_service.service.as_deref().filter(|s| *s != "*");
+                if self
+                    .find_deny(&candidates, &domain, &mxid, service, None, permission_int)?
+                    .is_none()
+                {
                     return Ok(true);
                 }
             }
@@ -0,0 +1,901 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use napi::bindgen_prelude::{Error as JsError, Status};
+
+use crate::feeds::parser::{content_hash, FeedItem, JsRssChannel};
+use crate::feeds::windows_zones::windows_zone_to_iana;
+use crate::format_util::hash_id;
+
+/// How far into the future recurring events are expanded.
+const RECURRENCE_WINDOW_DAYS: i64 = 30;
+/// The maximum number of occurrences emitted for a single recurring event.
+const MAX_OCCURRENCES_PER_EVENT: usize = 50;
+/// The maximum number of recurrence steps taken before giving up on a rule.
+const MAX_RECURRENCE_ITERATIONS: usize = 1_000;
+
+struct Property {
+    name: String,
+    params: HashMap<String, String>,
+    value: String,
+}
+
+/// A STANDARD or DAYLIGHT component of a VTIMEZONE.
+#[derive(Default)]
+struct Observance {
+    /// The local time of the first onset.
+    start: NaiveDateTime,
+    /// The UTC offset in seconds before each onset.
+    offset_from: i32,
+    /// The UTC offset in seconds after each onset.
+    offset_to: i32,
+    rule: Option<RecurrenceRule>,
+}
+
+impl Observance {
+    /// Find the most recent onset at or before a local time.
+    fn last_onset(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        if self.start > time {
+            return None;
+        }
+        // Observances recur yearly in practice, so other rules are treated as a single onset.
+        let Some(rule) = self.rule.as_ref().filter(|r| r.freq == Frequency::Yearly) else {
+            return Some(self.start);
+        };
+        let month = rule.by_month.first().copied().unwrap_or(self.start.month());
+        [time.year(), time.year() - 1]
+            .into_iter()
+            .find_map(|year| {
+                let day = *month_days(rule, year, month, self.start.day()).first()?;
+                let onset = NaiveDate::from_ymd_opt(year, month, day)?.and_time(self.start.time());
+                (onset <= time && rule.until.is_none_or(|u| onset.and_utc() <= u)).then_some(onset)
+            })
+            .or(Some(self.start))
+    }
+}
+
+/// A timezone defined by a VTIMEZONE component, for zones not in the tz database.
+struct CustomZone {
+    observances: Vec<Observance>,
+}
+
+impl CustomZone {
+    /// The UTC offset in seconds for a local time.
+    fn offset_at(&self, time: NaiveDateTime) -> i32 {
+        self.observances
+            .iter()
+            .filter_map(|o| Some((o.last_onset(time)?, o.offset_to)))
+            .max_by_key(|(onset, _)| *onset)
+            .map(|(_, offset)| offset)
+            // Before the first onset, use the offset that was in effect before it.
+            .or_else(|| {
+                self.observances
+                    .iter()
+                    .min_by_key(|o| o.start)
+                    .map(|o| o.offset_from)
+            })
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Clone)]
+enum Zone {
+    Named(Tz),
+    Custom(Arc<CustomZone>),
+}
+
+impl Zone {
+    fn to_utc(&self, time: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            Zone::Named(tz) => tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc))
+                // Times that don't exist (DST gaps) are treated as UTC.
+                .unwrap_or_else(|| time.and_utc()),
+            Zone::Custom(zone) => time
+                .checked_sub_signed(Duration::seconds(zone.offset_at(time) as i64))
+                .unwrap_or(time)
+                .and_utc(),
+        }
+    }
+}
+
+/// The timezones that times in a calendar may refer to.
+struct Timezones {
+    /// The zone used for floating times, and for TZIDs that can't be resolved.
+    default: Zone,
+    /// Zones defined by VTIMEZONE components, by TZID.
+    defined: HashMap<String, Zone>,
+}
+
+impl Timezones {
+    fn resolve(&self, tzid: Option<&String>) -> Zone {
+        tzid.and_then(|tzid| {
+            self.defined
+                .get(tzid)
+                .cloned()
+                .or_else(|| named_zone(tzid).map(Zone::Named))
+        })
+        .unwrap_or_else(|| self.default.clone())
+    }
+}
+
+/// Find a zone in the tz database by its IANA or Windows name.
+fn named_zone(name: &str) -> Option<Tz> {
+    let name = name.trim();
+    name.parse::<Tz>()
+        .ok()
+        .or_else(|| windows_zone_to_iana(name)?.parse().ok())
+        // Some producers prefix the IANA name with a path, e.g. `/mozilla.org/20050126_1/Europe/London`.
+        .or_else(|| {
+            name.match_indices('/')
+                .find_map(|(i, _)| name[i + 1..].parse().ok())
+        })
+}
+
+#[derive(Clone)]
+enum EventTime {
+    /// An all day event.
+    Date(NaiveDate),
+    /// A wall clock time in a given timezone.
+    Local(NaiveDateTime, Zone),
+}
+
+impl EventTime {
+    fn to_utc(&self) -> DateTime<Utc> {
+        match self {
+            EventTime::Date(date) => date.and_time(NaiveTime::MIN).and_utc(),
+            EventTime::Local(time, zone) => zone.to_utc(*time),
+        }
+    }
+
+    fn naive(&self) -> NaiveDateTime {
+        match self {
+            EventTime::Date(date) => date.and_time(NaiveTime::MIN),
+            EventTime::Local(time, _) => *time,
+        }
+    }
+
+    fn with_naive(&self, time: NaiveDateTime) -> Self {
+        match self {
+            EventTime::Date(_) => EventTime::Date(time.date()),
+            EventTime::Local(_, zone) => EventTime::Local(time, zone.clone()),
+        }
+    }
+
+    fn to_js_string(&self) -> String {
+        match self {
+            EventTime::Date(date) => date.format("%Y-%m-%d").to_string(),
+            EventTime::Local(..) => self.to_utc().to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Event {
+    uid: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    location: Option<String>,
+    url: Option<String>,
+    organizer: Option<String>,
+    last_modified: Option<String>,
+    start: Option<EventTime>,
+    end: Option<EventTime>,
+    duration: Option<Duration>,
+    rrule: Option<String>,
+    exdates: Vec<EventTime>,
+    recurrence_id: Option<EventTime>,
+}
+
+#[derive(PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+struct RecurrenceRule {
+    freq: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<DateTime<Utc>>,
+    /// Weekdays with an optional ordinal (e.g. `-1FR` for the last Friday).
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month_day: Vec<i32>,
+    /// Only used for the onsets of VTIMEZONE observances.
+    by_month: Vec<u32>,
+}
+
+/// Returns true if the given body looks like an iCalendar document.
+pub fn is_ics(body: &str) -> bool {
+    body.trim_start_matches('\u{feff}')
+        .trim_start()
+        .get(..15)
+        .is_some_and(|start| start.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+}
+
+/// Unfold content lines, as described in RFC 5545 section 3.1.
+fn unfold_lines(body: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in body.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let Some(continuation) = line.strip_prefix([' ', '\t']) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(continuation);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // The value starts at the first colon which is not inside a quoted parameter.
+    let mut in_quotes = false;
+    let split = line.char_indices().find(|(_, c)| {
+        if *c == '"' {
+            in_quotes = !in_quotes;
+        }
+        *c == ':' && !in_quotes
+    })?;
+    let (head, value) = (&line[..split.0], &line[split.0 + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('='))
+        .map(|(k, v)| (k.to_ascii_uppercase(), v.trim_matches('"').to_string()))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parse a date, a UTC time or a local time in the given zone.
+fn parse_time_value(
+    value: &str,
+    date_only: bool,
+    zone: impl FnOnce() -> Zone,
+) -> Option<EventTime> {
+    let value = value.trim();
+    if date_only || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(EventTime::Date);
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|t| EventTime::Local(t, Zone::Named(Tz::UTC)));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|t| EventTime::Local(t, zone()))
+}
+
+fn parse_times(property: &Property, timezones: &Timezones) -> Vec<EventTime> {
+    let date_only = property.params.get("VALUE").is_some_and(|v| v == "DATE");
+    property
+        .value
+        .split(',')
+        .filter_map(|value| {
+            parse_time_value(value, date_only, || {
+                timezones.resolve(property.params.get("TZID"))
+            })
+        })
+        .collect()
+}
+
+fn parse_time(property: &Property, timezones: &Timezones) -> Option<EventTime> {
+    parse_times(property, timezones).into_iter().next()
+}
+
+/// Parse a UTC offset such as `-0800` or `+053000` into seconds.
+fn parse_utc_offset(value: &str) -> Option<i32> {
+    let value = value.trim();
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    if !matches!(digits.len(), 4 | 6) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| digits.get(range).map_or(Ok(0), str::parse::<i32>);
+    Some(sign * (field(0..2).ok()? * 3600 + field(2..4).ok()? * 60 + field(4..6).ok()?))
+}
+
+/// Parse a duration such as `P1DT2H30M` or `PT15M`. Returns `None` if it is out of range.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => continue,
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                let part = match unit {
+                    'W' => Duration::try_weeks(n),
+                    'D' => Duration::try_days(n),
+                    'H' => Duration::try_hours(n),
+                    'M' => Duration::try_minutes(n),
+                    'S' => Duration::try_seconds(n),
+                    _ => return None,
+                };
+                duration = duration.checked_add(&part?)?;
+            }
+        }
+    }
+    Some(if negative { -duration } else { duration })
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Parse a recurrence rule. A local `UNTIL` time is taken to be in `zone`.
+fn parse_rrule(value: &str, zone: &Zone) -> Option<RecurrenceRule> {
+    let mut rule = RecurrenceRule {
+        freq: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: Vec::new(),
+        by_month_day: Vec::new(),
+        by_month: Vec::new(),
+    };
+    let mut freq = None;
+    for (key, value) in value.split(';').filter_map(|p| p.split_once('=')) {
+        match key.to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                freq = match value.to_ascii_uppercase().as_str() {
+                    "DAILY" => Some(Frequency::Daily),
+                    "WEEKLY" => Some(Frequency::Weekly),
+                    "MONTHLY" => Some(Frequency::Monthly),
+                    "YEARLY" => Some(Frequency::Yearly),
+                    // Sub-daily recurrence is not supported.
+                    _ => return None,
+                }
+            }
+            "INTERVAL" => rule.interval = value.parse().ok().filter(|i| *i > 0)?,
+            "COUNT" => rule.count = value.parse().ok(),
+            "UNTIL" => {
+                rule.until = parse_time_value(value, false, || zone.clone()).map(|t| t.to_utc())
+            }
+            "BYDAY" => {
+                for day in value.split(',') {
+                    // The weekday is the last two characters, after an optional ordinal.
+                    let split = day.char_indices().rev().nth(1).map_or(0, |(i, _)| i);
+                    let (ordinal, weekday) = day.split_at(split);
+                    rule.by_day
+                        .push((ordinal.parse().ok(), parse_weekday(weekday)?));
+                }
+            }
+            "BYMONTHDAY" => {
+                rule.by_month_day = value.split(',').filter_map(|d| d.parse().ok()).collect()
+            }
+            "BYMONTH" => {
+                rule.by_month = value
+                    .split(',')
+                    .filter_map(|m| m.parse().ok())
+                    .filter(|m| (1..=12).contains(m))
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+    rule.freq = freq?;
+    Some(rule)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|d| d.pred_opt())
+        .map_or(28, |d| d.day())
+}
+
+/// Find the days in a month matching the rule, in ascending order.
+fn month_days(rule: &RecurrenceRule, year: i32, month: u32, start_day: u32) -> Vec<u32> {
+    let last_day = days_in_month(year, month);
+    let mut days: Vec<u32> = if !rule.by_month_day.is_empty() {
+        rule.by_month_day
+            .iter()
+            .filter_map(|d| match *d {
+                d if d > 0 => Some(d as u32),
+                d if d < 0 => (last_day as i32 + d + 1).try_into().ok(),
+                _ => None,
+            })
+            .collect()
+    } else if !rule.by_day.is_empty() {
+        let mut days = Vec::new();
+        for (ordinal, weekday) in rule.by_day.iter() {
+            let matching: Vec<u32> = (1..=last_day)
+                .filter(|d| {
+                    NaiveDate::from_ymd_opt(year, month, *d)
+                        .is_some_and(|d| d.weekday() == *weekday)
+                })
+                .collect();
+            match ordinal {
+                Some(n) if *n > 0 => days.extend(matching.get(*n as usize - 1)),
+                Some(n) if *n < 0 => days.extend(
+                    matching
+                        .len()
+                        .checked_sub(n.unsigned_abs() as usize)
+                        .map(|i| matching[i]),
+                ),
+                _ => days.extend(matching),
+            }
+        }
+        days
+    } else {
+        vec![start_day]
+    };
+    days.retain(|d| *d >= 1 && *d <= last_day);
+    days.sort_unstable();
+    days.dedup();
+    days
+}
+
+/// Expand a recurrence rule into the wall clock start times of each occurrence, in order.
+///
+/// Expansion happens on the naive local time so that events keep their wall clock
+/// time across daylight saving changes.
+fn expand_rrule(
+    rule: &RecurrenceRule,
+    start: &EventTime,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+) -> Vec<EventTime> {
+    let first = start.naive();
+    let mut occurrences = Vec::new();
+    let weekdays = {
+        let mut days: Vec<Weekday> = rule.by_day.iter().map(|(_, d)| *d).collect();
+        if days.is_empty() {
+            days.push(first.weekday());
+        }
+        days.sort_by_key(|d| d.num_days_from_monday());
+        days.dedup();
+        days
+    };
+    // Skip straight to the periods just before the window rather than walking from
+    // the first one. Daily and weekly rules have a fixed number of occurrences in each
+    // period, so the occurrences skipped can still be counted towards a COUNT. Other
+    // rules with a COUNT are walked from the start, which covers at least 80 years.
+    let skippable =
+        rule.count.is_none() || matches!(rule.freq, Frequency::Daily | Frequency::Weekly);
+    let first_period = if skippable {
+        let elapsed = window_start.naive_utc() - first;
+        let periods = match rule.freq {
+            Frequency::Daily => elapsed.num_days(),
+            Frequency::Weekly => elapsed.num_weeks(),
+            Frequency::Monthly => elapsed.num_days() / 31,
+            Frequency::Yearly => elapsed.num_days() / 366,
+        };
+        (periods / rule.interval as i64 - 1).max(0)
+    } else {
+        0
+    };
+    let mut counted = match (first_period, &rule.freq) {
+        (0, _) => 0,
+        (periods, Frequency::Weekly) => {
+            // The first week only includes the days on or after the start.
+            let first_week = weekdays
+                .iter()
+                .filter(|d| d.num_days_from_monday() >= first.weekday().num_days_from_monday())
+                .count();
+            first_week + weekdays.len() * (periods as usize - 1)
+        }
+        (periods, _) => periods as usize,
+    };
+    if rule.count.is_some_and(|c| counted >= c) {
+        return occurrences;
+    }
+    for period in first_period..first_period + MAX_RECURRENCE_ITERATIONS as i64 {
+        // Periods beyond the range of dates end the expansion.
+        let Some(candidates) = period_candidates(rule, first, &weekdays, period) else {
+            return occurrences;
+        };
+        for candidate in candidates {
+            if candidate < first {
+                continue;
+            }
+            let occurrence = start.with_naive(candidate);
+            let occurrence_utc = occurrence.to_utc();
+            if occurrence_utc > window_end || rule.until.is_some_and(|u| occurrence_utc > u) {
+                return occurrences;
+            }
+            occurrences.push(occurrence);
+            counted += 1;
+            if rule.count.is_some_and(|c| counted >= c) {
+                return occurrences;
+            }
+        }
+    }
+    occurrences
+}
+
+/// The occurrences of a rule within one period, or `None` if the period is out of range.
+fn period_candidates(
+    rule: &RecurrenceRule,
+    first: NaiveDateTime,
+    weekdays: &[Weekday],
+    period: i64,
+) -> Option<Vec<NaiveDateTime>> {
+    let time = first.time();
+    let periods = period.checked_mul(rule.interval as i64)?;
+    Some(match rule.freq {
+        Frequency::Daily => vec![first.checked_add_signed(Duration::try_days(periods)?)?],
+        Frequency::Weekly => {
+            let week_start = first
+                .date()
+                .checked_sub_signed(Duration::days(first.weekday().num_days_from_monday() as i64))?
+                .checked_add_signed(Duration::try_weeks(periods)?)?;
+            weekdays
+                .iter()
+                .map(|d| {
+                    week_start
+                        .checked_add_signed(Duration::days(d.num_days_from_monday() as i64))
+                        .map(|d| d.and_time(time))
+                })
+                .collect::<Option<_>>()?
+        }
+        Frequency::Monthly => {
+            let months = periods.checked_add(first.month0() as i64)?;
+            let year = first.year().checked_add(i32::try_from(months / 12).ok()?)?;
+            let month = (months % 12) as u32 + 1;
+            month_days(rule, year, month, first.day())
+                .into_iter()
+                .filter_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+                .map(|d| d.and_time(time))
+                .collect()
+        }
+        Frequency::Yearly => {
+            let year = first.year().checked_add(i32::try_from(periods).ok()?)?;
+            NaiveDate::from_ymd_opt(year, first.month(), first.day())
+                .map(|d| d.and_time(time))
+                .into_iter()
+                .collect()
+        }
+    })
+}
+
+/// A stable identifier for an event. Events without a UID are identified by their
+/// summary and start time.
+fn event_id(event: &Event) -> String {
+    match &event.uid {
+        Some(uid) => uid.clone(),
+        None => format!(
+            "{}\0{}",
+            event.summary.as_deref().unwrap_or_default(),
+            event
+                .start
+                .as_ref()
+                .map(EventTime::to_js_string)
+                .unwrap_or_default()
+        ),
+    }
+}
+
+fn event_to_feed_item(
+    event: &Event,
+    start: &EventTime,
+    end: Option<EventTime>,
+    occurrence_key: Option<&EventTime>,
+) -> FeedItem {
+    let id = event_id(event);
+    let key = match occurrence_key {
+        Some(occurrence) => format!("{}/{}", id, occurrence.to_utc().to_rfc3339()),
+        None => id,
+    };
+    let start_string = start.to_js_string();
+    FeedItem {
+        title: event.summary.clone(),
+        link: event.url.clone(),
+        id: event.uid.clone(),
+        id_is_permalink: false,
+        pubdate: Some(start.to_utc().to_rfc2822()),
+        summary: event.description.clone(),
+        author: event.organizer.clone(),
+        hash_id: hash_id(key).ok().map(|f| format!("md5:{}", f)),
+        content_hash: content_hash(
+            event.summary.as_deref(),
+            event.description.as_deref(),
+            Some(
+                event
+                    .last_modified
+                    .as_deref()
+                    .unwrap_or(start_string.as_str()),
+            ),
+        ),
+        start: Some(start_string),
+        end: end.as_ref().map(EventTime::to_js_string),
+        location: event.location.clone(),
+        podcast: None,
+    }
+}
+
+/// A VTIMEZONE component.
+#[derive(Default)]
+struct TimezoneDefinition {
+    tzid: Option<String>,
+    location: Option<String>,
+    observances: Vec<Observance>,
+}
+
+impl TimezoneDefinition {
+    fn into_zone(self) -> Option<Zone> {
+        // Prefer the tz database where the zone is known to it, as it has the full history.
+        [self.tzid.as_deref(), self.location.as_deref()]
+            .into_iter()
+            .flatten()
+            .find_map(named_zone)
+            .map(Zone::Named)
+            .or_else(|| {
+                (!self.observances.is_empty()).then(|| {
+                    Zone::Custom(Arc::new(CustomZone {
+                        observances: self.observances,
+                    }))
+                })
+            })
+    }
+}
+
+fn parse_timezones(properties: &[Property]) -> HashMap<String, Zone> {
+    let mut timezones = HashMap::new();
+    let mut definition: Option<TimezoneDefinition> = None;
+    let mut observance: Option<Observance> = None;
+    for property in properties {
+        let value = property.value.trim();
+        match property.name.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VTIMEZONE") => {
+                definition = Some(TimezoneDefinition::default())
+            }
+            "BEGIN"
+                if definition.is_some()
+                    && (value.eq_ignore_ascii_case("STANDARD")
+                        || value.eq_ignore_ascii_case("DAYLIGHT")) =>
+            {
+                observance = Some(Observance::default())
+            }
+            "END" if value.eq_ignore_ascii_case("VTIMEZONE") => {
+                if let Some(definition) = definition.take() {
+                    if let Some(tzid) = definition.tzid.clone() {
+                        timezones.extend(definition.into_zone().map(|zone| (tzid, zone)));
+                    }
+                }
+            }
+            "END" => {
+                if let (Some(definition), Some(observance)) =
+                    (definition.as_mut(), observance.take())
+                {
+                    definition.observances.push(observance);
+                }
+            }
+            name => {
+                let Some(definition) = definition.as_mut() else {
+                    continue;
+                };
+                let Some(observance) = observance.as_mut() else {
+                    match name {
+                        "TZID" => definition.tzid = Some(value.to_string()),
+                        "X-LIC-LOCATION" => definition.location = Some(value.to_string()),
+                        _ => {}
+                    }
+                    continue;
+                };
+                match name {
+                    "DTSTART" => {
+                        observance.start = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+                            .unwrap_or_default()
+                    }
+                    "TZOFFSETFROM" => {
+                        observance.offset_from = parse_utc_offset(value).unwrap_or_default()
+                    }
+                    "TZOFFSETTO" => {
+                        observance.offset_to = parse_utc_offset(value).unwrap_or_default()
+                    }
+                    "RRULE" => observance.rule = parse_rrule(value, &Zone::Named(Tz::UTC)),
+                    _ => {}
+                }
+            }
+        }
+    }
+    timezones
+}
+
+fn parse_events(body: &str) -> Result<(Option<String>, Vec<Event>), JsError> {
+    let properties: Vec<Property> = unfold_lines(body)
+        .iter()
+        .filter_map(|line| parse_property(line))
+        .collect();
+    let mut timezones = Timezones {
+        default: Zone::Named(Tz::UTC),
+        defined: parse_timezones(&properties),
+    };
+    let mut calendar_name = None;
+    let mut events = Vec::new();
+    let mut current: Option<Event> = None;
+    // Components nested inside an event (e.g. VALARM) or the calendar (e.g. VTIMEZONE)
+    // that we are skipping over.
+    let mut skip_depth = 0;
+    let mut found_calendar = false;
+
+    for property in properties.iter() {
+        let value = property.value.trim();
+        match property.name.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VCALENDAR") => found_calendar = true,
+            "BEGIN" if value.eq_ignore_ascii_case("VEVENT") && skip_depth == 0 => {
+                current = Some(Event::default())
+            }
+            "BEGIN" => skip_depth += 1,
+            "END" if value.eq_ignore_ascii_case("VEVENT") && skip_depth == 0 => {
+                if let Some(event) = current.take() {
+                    events.push(event);
+                }
+            }
+            "END" if skip_depth > 0 => skip_depth -= 1,
+            _ if skip_depth > 0 => {}
+            "X-WR-CALNAME" if current.is_none() => calendar_name = Some(unescape_text(value)),
+            "X-WR-TIMEZONE" if current.is_none() => {
+                timezones.default = timezones.resolve(Some(&value.to_string()));
+            }
+            name => {
+                let Some(event) = current.as_mut() else {
+                    continue;
+                };
+                match name {
+                    "UID" => event.uid = Some(value.to_string()),
+                    "SUMMARY" => event.summary = Some(unescape_text(value)),
+                    "DESCRIPTION" => event.description = Some(unescape_text(value)),
+                    "LOCATION" => event.location = Some(unescape_text(value)),
+                    "URL" => event.url = Some(value.to_string()),
+                    "ORGANIZER" => {
+                        event.organizer = property
+                            .params
+                            .get("CN")
+                            .cloned()
+                            .or_else(|| Some(value.trim_start_matches("mailto:").to_string()))
+                    }
+                    "LAST-MODIFIED" => event.last_modified = Some(value.to_string()),
+                    "DTSTART" => event.start = parse_time(property, &timezones),
+                    "DTEND" => event.end = parse_time(property, &timezones),
+                    "DURATION" => event.duration = parse_duration(value),
+                    "RRULE" => event.rrule = Some(value.to_string()),
+                    "EXDATE" => event.exdates.extend(parse_times(property, &timezones)),
+                    "RECURRENCE-ID" => event.recurrence_id = parse_time(property, &timezones),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    if !found_calendar {
+        return Err(JsError::new(
+            Status::Unknown,
+            "Calendar did not contain a VCALENDAR component",
+        ));
+    }
+    Ok((calendar_name, events))
+}
+
+pub fn parse_ics_to_js_result(body: &str, now: DateTime<Utc>) -> Result<JsRssChannel, JsError> {
+    let (calendar_name, events) = parse_events(body)?;
+    let window_end = now + Duration::days(RECURRENCE_WINDOW_DAYS);
+
+    // Instances of a recurring event that have been individually modified.
+    let overridden: HashSet<(String, DateTime<Utc>)> = events
+        .iter()
+        .filter_map(|e| Some((e.uid.clone()?, e.recurrence_id.as_ref()?.to_utc())))
+        .collect();
+
+    let mut items = Vec::new();
+    for event in events.iter() {
+        let Some(start) = event.start.as_ref() else {
+            continue;
+        };
+        let length = match (event.end.as_ref(), event.duration) {
+            (Some(end), _) => Some(end.naive() - start.naive()),
+            (None, Some(duration)) => Some(duration),
+            (None, None) => None,
+        };
+        let end_for = |s: &EventTime| {
+            length.and_then(|l| Some(s.with_naive(s.naive().checked_add_signed(l)?)))
+        };
+
+        if let Some(recurrence_id) = event.recurrence_id.as_ref() {
+            items.push(event_to_feed_item(
+                event,
+                start,
+                end_for(start),
+                Some(recurrence_id),
+            ));
+            continue;
+        }
+
+        let Some(rule) = event
+            .rrule
+            .as_deref()
+            .and_then(|r| parse_rrule(r, &zone_of(start)))
+        else {
+            items.push(event_to_feed_item(event, start, end_for(start), None));
+            continue;
+        };
+
+        let exdates: HashSet<DateTime<Utc>> = event.exdates.iter().map(|e| e.to_utc()).collect();
+        let uid = event.uid.clone().unwrap_or_default();
+        items.extend(
+            expand_rrule(&rule, start, now, window_end)
+                .into_iter()
+                .filter(|o| {
+                    let start_utc = o.to_utc();
+                    // Only announce upcoming (or ongoing) occurrences.
+                    end_for(o).map_or(start_utc, |e| e.to_utc()) >= now
+                        && !exdates.contains(&start_utc)
+                        && !overridden.contains(&(uid.clone(), start_utc))
+                })
+                .take(MAX_OCCURRENCES_PER_EVENT)
+                .map(|o| event_to_feed_item(event, &o, end_for(&o), Some(&o))),
+        );
+    }
+
+    Ok(JsRssChannel {
+        title: calendar_name.unwrap_or_default(),
+        items,
+        image: None,
+        explicit: None,
+    })
+}
+
+fn zone_of(time: &EventTime) -> Zone {
+    match time {
+        EventTime::Date(_) => Zone::Named(Tz::UTC),
+        EventTime::Local(_, zone) => zone.clone(),
+    }
+}
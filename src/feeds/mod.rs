@@ -1 +1,4 @@
+pub mod ics;
+pub mod page_watch;
 pub mod parser;
+pub mod windows_zones;
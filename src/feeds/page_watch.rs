@@ -0,0 +1,165 @@
+use chrono::Utc;
+use napi::bindgen_prelude::{Error as JsError, Status};
+use scraper::{Html, Selector};
+use similar::{ChangeTag, TextDiff};
+
+use crate::feeds::parser::{
+    content_hash, fetch_with_cache, FeedItem, JsRssChannel, ReadFeedOptions,
+};
+
+/// The maximum number of changed lines included in the summary of a change.
+const MAX_DIFF_LINES: usize = 20;
+
+#[derive(Serialize, Debug, Deserialize)]
+#[napi(object)]
+pub struct PageWatchOptions {
+    /// A CSS selector for the part of the page to watch. Defaults to the `body`.
+    pub selector: Option<String>,
+    /// The normalised text of the page from the previous successful read.
+    pub previous_text: Option<String>,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+#[napi(object)]
+pub struct PageWatchResult {
+    /// A feed containing a single item describing the change, if the watched content changed.
+    pub feed: Option<JsRssChannel>,
+    /// The normalised text of the watched content, to be passed back in on the next read.
+    /// If the page was not modified this is the `previousText` that was passed in.
+    pub text: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Extract the text of all elements matching the selector, one line per text node
+/// with whitespace collapsed.
+fn extract_text(document: &Html, selector: &str) -> Result<String, JsError> {
+    let selector = Selector::parse(selector).map_err(|err| {
+        JsError::new(
+            Status::InvalidArg,
+            format!("Invalid selector '{}': {}", selector, err),
+        )
+    })?;
+    let mut lines = Vec::new();
+    let mut matched = false;
+    for element in document.select(&selector) {
+        matched = true;
+        lines.extend(
+            element
+                .text()
+                .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|t| !t.is_empty()),
+        );
+    }
+    if !matched {
+        return Err(JsError::new(
+            Status::Unknown,
+            "Selector did not match any content on the page",
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Produce a short, line based summary of the differences between two texts.
+fn summarise_diff(previous: &str, current: &str) -> String {
+    // Terminate both texts so a change to the last line isn't reported twice.
+    let (previous, current) = (format!("{}\n", previous), format!("{}\n", current));
+    let diff = TextDiff::from_lines(&previous, &current);
+    let changes: Vec<String> = diff
+        .iter_all_changes()
+        .filter_map(|change| match change.tag() {
+            ChangeTag::Delete => Some(format!("- {}", change.value().trim_end())),
+            ChangeTag::Insert => Some(format!("+ {}", change.value().trim_end())),
+            ChangeTag::Equal => None,
+        })
+        .collect();
+    let mut summary = changes
+        .iter()
+        .take(MAX_DIFF_LINES)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if changes.len() > MAX_DIFF_LINES {
+        summary.push_str(&format!(
+            "\n… and {} more changed lines",
+            changes.len() - MAX_DIFF_LINES
+        ));
+    }
+    summary
+}
+
+fn parse_page_watch(
+    url: &str,
+    html: &str,
+    options: &PageWatchOptions,
+) -> Result<(Option<JsRssChannel>, String), JsError> {
+    let document = Html::parse_document(html);
+    let text = extract_text(&document, options.selector.as_deref().unwrap_or("body"))?;
+    let title = Selector::parse("title")
+        .ok()
+        .and_then(|s| document.select(&s).next())
+        .map(|t| t.text().collect::<String>().trim().to_string())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| url.to_string());
+
+    let previous = match options.previous_text.as_deref() {
+        // Nothing to compare against on the first read.
+        None => return Ok((None, text)),
+        Some(previous) if previous == text => return Ok((None, text)),
+        Some(previous) => previous,
+    };
+
+    let now = Utc::now();
+    let fingerprint = content_hash(Some(&title), Some(&text), None);
+    let item = FeedItem {
+        title: Some(format!("{} has changed", title)),
+        link: Some(url.to_string()),
+        id: None,
+        id_is_permalink: false,
+        pubdate: Some(now.to_rfc2822()),
+        summary: Some(summarise_diff(previous, &text)),
+        author: None,
+        // A page may change back to content it had before, so each change is identified by
+        // the time it was seen as well as the content.
+        hash_id: content_hash(Some(previous), Some(&text), Some(&now.to_rfc3339())),
+        content_hash: fingerprint,
+        start: None,
+        end: None,
+        location: None,
+        podcast: None,
+    };
+    Ok((
+        Some(JsRssChannel {
+            title,
+            items: vec![item],
+            image: None,
+            explicit: None,
+        }),
+        text,
+    ))
+}
+
+/// Read a HTML page and report any changes to the content matched by the selector.
+#[napi(js_name = "readPageWatch")]
+pub async fn js_read_page_watch(
+    url: String,
+    watch_options: PageWatchOptions,
+    options: ReadFeedOptions,
+) -> Result<PageWatchResult, JsError> {
+    let response = fetch_with_cache(url.clone(), options).await?;
+    let Some(body) = response.body else {
+        return Ok(PageWatchResult {
+            feed: None,
+            text: watch_options.previous_text,
+            etag: None,
+            last_modified: None,
+        });
+    };
+    let (feed, text) = parse_page_watch(&url, &body, &watch_options)?;
+    Ok(PageWatchResult {
+        feed,
+        text: Some(text),
+        etag: response.etag,
+        last_modified: response.last_modified,
+    })
+}
@@ -1,6 +1,7 @@
-use std::{str::FromStr, time::Duration};
+use std::{collections::HashMap, str::FromStr, time::Duration};
 
 use atom_syndication::{Error as AtomError, Feed, Person};
+use chrono::Utc;
 use napi::bindgen_prelude::{Error as JsError, Status};
 use reqwest::{
     header::{HeaderMap, HeaderValue},
@@ -8,6 +9,7 @@ use reqwest::{
 };
 use rss::{Channel, Error as RssError};
 
+use crate::feeds::ics::{is_ics, parse_ics_to_js_result};
 use crate::format_util::hash_id;
 
 #[derive(Serialize, Debug, Deserialize)]
@@ -21,6 +23,57 @@ pub struct FeedItem {
     pub summary: Option<String>,
     pub author: Option<String>,
     pub hash_id: Option<String>,
+    /**
+     * A fingerprint of the title, summary and updated timestamp of the item,
+     * used to detect when an already seen item has been changed.
+     */
+    pub content_hash: Option<String>,
+    /**
+     * The start time of a calendar event, as an RFC3339 timestamp or a date for all day events.
+     */
+    pub start: Option<String>,
+    /**
+     * The end time of a calendar event, in the same format as `start`.
+     */
+    pub end: Option<String>,
+    pub location: Option<String>,
+    /**
+     * Episode metadata for podcast feeds, taken from the iTunes namespace.
+     */
+    pub podcast: Option<PodcastEpisode>,
+}
+
+#[derive(Serialize, Debug, Deserialize)]
+#[napi(object)]
+pub struct PodcastEpisode {
+    pub duration_seconds: Option<u32>,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    /**
+     * The episode artwork.
+     */
+    pub image: Option<String>,
+    pub explicit: Option<bool>,
+    /**
+     * The URL of the episode media.
+     */
+    pub enclosure_url: Option<String>,
+}
+
+#[napi(string_enum)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum FeedItemState {
+    New,
+    Updated,
+    Unchanged,
+}
+
+#[derive(Debug)]
+#[napi(object)]
+pub struct FeedItemClassification {
+    pub hash_id: Option<String>,
+    pub content_hash: Option<String>,
+    pub state: FeedItemState,
 }
 
 #[derive(Serialize, Debug, Deserialize)]
@@ -28,6 +81,11 @@ pub struct FeedItem {
 pub struct JsRssChannel {
     pub title: String,
     pub items: Vec<FeedItem>,
+    /**
+     * The artwork for the feed, if any.
+     */
+    pub image: Option<String>,
+    pub explicit: Option<bool>,
 }
 
 #[derive(Serialize, Debug, Deserialize)]
@@ -47,9 +105,58 @@ pub struct FeedResult {
     pub last_modified: Option<String>,
 }
 
+pub(crate) fn content_hash(
+    title: Option<&str>,
+    summary: Option<&str>,
+    updated: Option<&str>,
+) -> Option<String> {
+    let fingerprint = [title, summary, updated]
+        .map(|v| v.unwrap_or_default())
+        .join("\0");
+    hash_id(fingerprint).ok().map(|f| format!("md5:{}", f))
+}
+
+/// Parse an `itunes:duration`, which may be given as seconds, `MM:SS` or `HH:MM:SS`.
+fn parse_itunes_duration(duration: &str) -> Option<u32> {
+    duration.trim().split(':').try_fold(0u32, |total, part| {
+        total
+            .checked_mul(60)?
+            .checked_add(part.parse::<f64>().ok()? as u32)
+    })
+}
+
+fn parse_itunes_explicit(explicit: &str) -> Option<bool> {
+    match explicit.trim().to_lowercase().as_str() {
+        "yes" | "true" | "explicit" => Some(true),
+        "no" | "false" | "clean" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_itunes_episode(item: &rss::Item) -> Option<PodcastEpisode> {
+    let itunes = item.itunes_ext()?;
+    Some(PodcastEpisode {
+        duration_seconds: itunes.duration().and_then(parse_itunes_duration),
+        season: itunes.season().and_then(|s| s.trim().parse().ok()),
+        episode: itunes.episode().and_then(|e| e.trim().parse().ok()),
+        image: itunes.image().map(String::from),
+        explicit: itunes.explicit().and_then(parse_itunes_explicit),
+        enclosure_url: item.enclosure().map(|e| e.url().to_string()),
+    })
+}
+
 fn parse_channel_to_js_result(channel: &Channel) -> JsRssChannel {
     JsRssChannel {
         title: channel.title().to_string(),
+        image: channel
+            .itunes_ext()
+            .and_then(|i| i.image())
+            .or(channel.image().map(|i| i.url()))
+            .map(String::from),
+        explicit: channel
+            .itunes_ext()
+            .and_then(|i| i.explicit())
+            .and_then(parse_itunes_explicit),
         items: channel
             .items()
             .iter()
@@ -72,6 +179,11 @@ fn parse_channel_to_js_result(channel: &Channel) -> JsRssChannel {
                     .or(item.title.clone())
                     .and_then(|f| hash_id(f).ok())
                     .map(|f| format!("md5:{}", f)),
+                content_hash: content_hash(item.title(), item.description(), item.pub_date()),
+                start: None,
+                end: None,
+                location: None,
+                podcast: parse_itunes_episode(item),
             })
             .collect(),
     }
@@ -98,6 +210,8 @@ fn parse_feed_to_js_result(feed: &Feed) -> JsRssChannel {
     }
     JsRssChannel {
         title: feed.title().to_string(),
+        image: feed.logo().or(feed.icon()).map(String::from),
+        explicit: None,
         items: feed
             .entries()
             .iter()
@@ -119,13 +233,55 @@ fn parse_feed_to_js_result(feed: &Feed) -> JsRssChannel {
                 summary: item.summary().map(|v| v.value.clone()),
                 author: authors_to_string(item.authors()),
                 hash_id: hash_id(item.id.clone()).ok().map(|f| format!("md5:{}", f)),
+                content_hash: content_hash(
+                    Some(item.title().value.as_str()),
+                    item.summary().map(|v| v.value.as_str()),
+                    Some(item.updated().to_rfc3339().as_str()),
+                ),
+                start: None,
+                end: None,
+                location: None,
+                podcast: None,
             })
             .collect(),
     }
 }
 
+/// Classify a set of feed items against previously stored state.
+///
+/// `seen` maps the `hash_id` of each previously seen item to the `content_hash`
+/// it had at the time. Entries stored before content hashes were tracked have no
+/// hash, and are always treated as unchanged so they are not re-announced.
+#[napi(js_name = "classifyFeedItems")]
+pub fn js_classify_feed_items(
+    items: Vec<FeedItem>,
+    seen: HashMap<String, Option<String>>,
+) -> Vec<FeedItemClassification> {
+    items
+        .into_iter()
+        .map(|item| {
+            let state = match item.hash_id.as_ref().and_then(|id| seen.get(id)) {
+                None => FeedItemState::New,
+                Some(None) => FeedItemState::Unchanged,
+                Some(Some(previous)) if Some(previous) == item.content_hash.as_ref() => {
+                    FeedItemState::Unchanged
+                }
+                Some(Some(_)) => FeedItemState::Updated,
+            };
+            FeedItemClassification {
+                hash_id: item.hash_id,
+                content_hash: item.content_hash,
+                state,
+            }
+        })
+        .collect()
+}
+
 #[napi(js_name = "parseFeed")]
 pub fn js_parse_feed(xml: String) -> Result<JsRssChannel, JsError> {
+    if is_ics(&xml) {
+        return parse_ics_to_js_result(&xml, Utc::now());
+    }
     match Channel::from_str(&xml) {
         Ok(channel) => Ok(parse_channel_to_js_result(&channel)),
         Err(RssError::InvalidStartTag) =>
@@ -173,8 +329,20 @@ pub fn js_parse_feed(xml: String) -> Result<JsRssChannel, JsError> {
     }
 }
 
-#[napi(js_name = "readFeed")]
-pub async fn js_read_feed(url: String, options: ReadFeedOptions) -> Result<FeedResult, JsError> {
+/// The body of a conditional request, along with the caching headers of the response.
+///
+/// `body` is `None` if the server reported the resource as not modified.
+pub(crate) struct CachedResponse {
+    pub body: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Fetch a URL, sending the cache headers from any previous request.
+pub(crate) async fn fetch_with_cache(
+    url: String,
+    options: ReadFeedOptions,
+) -> Result<CachedResponse, JsError> {
     let client = reqwest::Client::new();
     let req = client
         .request(Method::GET, url)
@@ -204,24 +372,21 @@ pub async fn js_read_feed(url: String, options: ReadFeedOptions) -> Result<FeedR
             let res_headers = res.headers().clone();
             match res.status() {
                 StatusCode::OK => match res.text().await {
-                    Ok(body) => match js_parse_feed(body) {
-                        Ok(feed) => Ok(FeedResult {
-                            feed: Some(feed),
-                            etag: res_headers
-                                .get("ETag")
-                                .map(|v| v.to_str().unwrap())
-                                .map(|v| v.to_string()),
-                            last_modified: res_headers
-                                .get("Last-Modified")
-                                .map(|v| v.to_str().unwrap())
-                                .map(|v| v.to_string()),
-                        }),
-                        Err(err) => Err(err),
-                    },
+                    Ok(body) => Ok(CachedResponse {
+                        body: Some(body),
+                        etag: res_headers
+                            .get("ETag")
+                            .map(|v| v.to_str().unwrap())
+                            .map(|v| v.to_string()),
+                        last_modified: res_headers
+                            .get("Last-Modified")
+                            .map(|v| v.to_str().unwrap())
+                            .map(|v| v.to_string()),
+                    }),
                     Err(err) => Err(JsError::new(Status::Unknown, err)),
                 },
-                StatusCode::NOT_MODIFIED => Ok(FeedResult {
-                    feed: None,
+                StatusCode::NOT_MODIFIED => Ok(CachedResponse {
+                    body: None,
                     etag: None,
                     last_modified: None,
                 }),
@@ -237,3 +402,13 @@ pub async fn js_read_feed(url: String, options: ReadFeedOptions) -> Result<FeedR
         )),
     }
 }
+
+#[napi(js_name = "readFeed")]
+pub async fn js_read_feed(url: String, options: ReadFeedOptions) -> Result<FeedResult, JsError> {
+    let response = fetch_with_cache(url, options).await?;
+    Ok(FeedResult {
+        feed: response.body.map(js_parse_feed).transpose()?,
+        etag: response.etag,
+        last_modified: response.last_modified,
+    })
+}
@@ -4,6 +4,7 @@ pub mod format_util;
 pub mod github;
 pub mod jira;
 pub mod tokens;
+pub mod unfurl;
 pub mod util;
 
 #[macro_use]
@@ -0,0 +1,253 @@
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use napi::bindgen_prelude::{Error as JsError, Status};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::CONTENT_TYPE,
+    redirect::Policy,
+    Method, StatusCode,
+};
+use scraper::{Html, Selector};
+use url::{Host, Url};
+
+const DEFAULT_TIMEOUT_SECONDS: u32 = 10;
+const DEFAULT_MAX_BODY_BYTES: u32 = 512 * 1024;
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Serialize, Debug, Deserialize)]
+#[napi(object)]
+pub struct UnfurlOptions {
+    pub user_agent: String,
+    pub timeout_seconds: Option<u32>,
+    /// The maximum number of bytes of the page to read. Metadata is expected to be in the `<head>`,
+    /// so anything after this is ignored.
+    pub max_body_bytes: Option<u32>,
+    /// Allow fetching links on loopback, private or link-local addresses. Defaults to false, as
+    /// links come from untrusted sources. Proxies set in the environment are only used when this
+    /// is true.
+    pub allow_private_addresses: Option<bool>,
+}
+
+#[derive(Serialize, Debug, Deserialize, Default)]
+#[napi(object)]
+pub struct UnfurlResult {
+    /// The canonical URL of the page, after redirects.
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// Returns true for addresses that are not on the public internet.
+fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Shared address space (100.64.0.0/10)
+                || (a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_address(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local (fc00::/7) and link-local (fe80::/10)
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+            }
+        },
+    }
+}
+
+/// Check that a URL may be unfurled, before requesting it or following a redirect to it.
+fn check_url(url: &Url, allow_private_addresses: bool) -> Result<(), String> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!("Cannot unfurl URLs with scheme '{}'", url.scheme()));
+    }
+    // Hostnames are checked by `PublicResolver` when they are resolved.
+    let ip = match url.host() {
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+        _ => return Ok(()),
+    };
+    if !allow_private_addresses && is_private_address(ip) {
+        return Err(format!("Cannot unfurl links to private address {}", ip));
+    }
+    Ok(())
+}
+
+/// A DNS resolver which refuses hostnames that resolve to private addresses.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let lookup = host.clone();
+            let addrs: Vec<SocketAddr> = napi::tokio::task::spawn_blocking(move || {
+                (lookup.as_str(), 0)
+                    .to_socket_addrs()
+                    .map(Iterator::collect)
+            })
+            .await??;
+            if let Some(addr) = addrs.iter().find(|a| is_private_address(a.ip())) {
+                return Err(format!(
+                    "Cannot unfurl links to {} as it resolves to private address {}",
+                    host,
+                    addr.ip()
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn parse_metadata(html: &str, base_url: &Url) -> UnfurlResult {
+    let document = Html::parse_document(html);
+    let meta_selector = Selector::parse("meta[content]").unwrap();
+    let title_selector = Selector::parse("title").unwrap();
+    let link_selector = Selector::parse("link[rel=canonical][href]").unwrap();
+
+    // Find the first non-empty value for a set of meta keys, in order of preference.
+    let find_meta = |keys: &[&str]| -> Option<String> {
+        keys.iter().find_map(|key| {
+            document.select(&meta_selector).find_map(|meta| {
+                let element = meta.value();
+                let name = element.attr("property").or(element.attr("name"))?;
+                if !name.eq_ignore_ascii_case(key) {
+                    return None;
+                }
+                let content = element.attr("content")?.trim();
+                (!content.is_empty()).then(|| content.to_string())
+            })
+        })
+    };
+    let resolve = |value: String| base_url.join(&value).map(|u| u.to_string()).ok();
+
+    UnfurlResult {
+        url: find_meta(&["og:url"])
+            .or_else(|| {
+                document
+                    .select(&link_selector)
+                    .next()
+                    .and_then(|l| l.value().attr("href"))
+                    .map(String::from)
+            })
+            .and_then(resolve)
+            .unwrap_or_else(|| base_url.to_string()),
+        title: find_meta(&["og:title", "twitter:title"]).or_else(|| {
+            document
+                .select(&title_selector)
+                .next()
+                .map(|t| t.text().collect::<String>().trim().to_string())
+                .filter(|t| !t.is_empty())
+        }),
+        description: find_meta(&["og:description", "twitter:description", "description"]),
+        image: find_meta(&[
+            "og:image:secure_url",
+            "og:image:url",
+            "og:image",
+            "twitter:image",
+            "twitter:image:src",
+        ])
+        .and_then(resolve),
+        site_name: find_meta(&["og:site_name", "application-name", "twitter:site"]),
+    }
+}
+
+/// Fetch a link and extract a preview from its OpenGraph, Twitter card and `<meta>` tags.
+#[napi(js_name = "unfurlLink")]
+pub async fn js_unfurl_link(url: String, options: UnfurlOptions) -> Result<UnfurlResult, JsError> {
+    let url = Url::parse(&url)
+        .map_err(|err| JsError::new(Status::InvalidArg, format!("Invalid URL: {}", err)))?;
+    let allow_private_addresses = options.allow_private_addresses.unwrap_or(false);
+    check_url(&url, allow_private_addresses)
+        .map_err(|err| JsError::new(Status::InvalidArg, err))?;
+    let mut client = reqwest::Client::builder()
+        .redirect(Policy::custom(move |attempt| {
+            if attempt.previous().len() > MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            match check_url(attempt.url(), allow_private_addresses) {
+                Ok(()) => attempt.follow(),
+                Err(err) => attempt.error(err),
+            }
+        }))
+        .user_agent(options.user_agent)
+        .timeout(Duration::from_secs(
+            options.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS) as u64,
+        ));
+    if !allow_private_addresses {
+        // A proxy would resolve hostnames itself, bypassing the resolver's checks.
+        client = client.dns_resolver(PublicResolver).no_proxy();
+    }
+    let client = client
+        .build()
+        .map_err(|err| JsError::new(Status::GenericFailure, err))?;
+    let max_body_bytes = options.max_body_bytes.unwrap_or(DEFAULT_MAX_BODY_BYTES) as usize;
+
+    let mut res = client
+        .request(Method::GET, url)
+        .header("Accept", "text/html,application/xhtml+xml")
+        .send()
+        .await
+        .map_err(|err| {
+            // Include the underlying cause, such as a refused redirect or address.
+            let mut message = err.to_string();
+            let mut source = std::error::Error::source(&err);
+            while let Some(cause) = source {
+                message = format!("{}: {}", message, cause);
+                source = cause.source();
+            }
+            JsError::new(
+                Status::Unknown,
+                format!("Failed to unfurl link due to HTTP error {}", message),
+            )
+        })?;
+    if res.status() != StatusCode::OK {
+        return Err(JsError::new(
+            Status::Unknown,
+            format!("Failed to unfurl link due to HTTP status {}", res.status()),
+        ));
+    }
+    let is_html = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/html") || v.contains("application/xhtml+xml"));
+    if !is_html {
+        return Err(JsError::new(
+            Status::Unknown,
+            "Failed to unfurl link as it is not a HTML page",
+        ));
+    }
+    let final_url = res.url().clone();
+
+    let mut body: Vec<u8> = Vec::new();
+    while let Some(chunk) = res
+        .chunk()
+        .await
+        .map_err(|err| JsError::new(Status::Unknown, err))?
+    {
+        body.extend_from_slice(&chunk);
+        if body.len() >= max_body_bytes {
+            body.truncate(max_body_bytes);
+            break;
+        }
+    }
+
+    Ok(parse_metadata(&String::from_utf8_lossy(&body), &final_url))
+}
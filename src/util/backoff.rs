@@ -0,0 +1,204 @@
+use rand::prelude::*;
+
+const DEFAULT_EXPONENTIAL_FACTOR: f64 = 2f64;
+const DEFAULT_DECORRELATED_FACTOR: f64 = 3f64;
+
+#[napi(string_enum)]
+#[derive(PartialEq, Eq)]
+pub enum BackoffStrategyKind {
+    /**
+     * `min * factor^(failures - 1)`, with full jitter by default.
+     */
+    Exponential,
+    /**
+     * A random duration between `min` and `factor` times the previous duration, with full jitter
+     * by default. Reducing the jitter moves the duration towards `factor` times the previous one.
+     */
+    DecorrelatedJitter,
+    /**
+     * `min + factor * (failures - 1)`.
+     */
+    Linear,
+    /**
+     * Always `min`.
+     */
+    Fixed,
+}
+
+#[napi(object)]
+pub struct BackoffStrategyOptions {
+    pub strategy: BackoffStrategyKind,
+    /**
+     * The minimum backoff duration in milliseconds.
+     */
+    pub min_ms: f64,
+    /**
+     * The maximum backoff duration in milliseconds.
+     */
+    pub max_ms: f64,
+    /**
+     * The growth factor. For `Exponential` this is the multiplier applied for each failure (default 2),
+     * for `DecorrelatedJitter` the multiplier of the previous duration (default 3), and for `Linear`
+     * the number of milliseconds added for each failure (defaults to `minMs`). Unused by `Fixed`.
+     */
+    pub factor: Option<f64>,
+    /**
+     * The proportion of the duration (0 to 1) that is randomised. Defaults to 1 (full jitter) for
+     * `Exponential` and `DecorrelatedJitter`, and 0 for `Linear` and `Fixed`.
+     */
+    pub jitter: Option<f64>,
+}
+
+/**
+ * Calculates how long an item should be backed off for.
+ */
+#[derive(Clone)]
+pub enum BackoffStrategy {
+    /**
+     * The original hookshot behaviour: `y * time + last^exponent` where `y` is between 0.5 and 1.5.
+     */
+    Classic {
+        time: f64,
+        exponent: f64,
+        max: f64,
+    },
+    Exponential {
+        min: f64,
+        max: f64,
+        factor: f64,
+        jitter: f64,
+    },
+    DecorrelatedJitter {
+        min: f64,
+        max: f64,
+        factor: f64,
+        jitter: f64,
+    },
+    Linear {
+        min: f64,
+        max: f64,
+        step: f64,
+        jitter: f64,
+    },
+    Fixed {
+        delay: f64,
+        max: f64,
+        jitter: f64,
+    },
+}
+
+impl TryFrom<BackoffStrategyOptions> for BackoffStrategy {
+    type Error = napi::Error;
+
+    fn try_from(options: BackoffStrategyOptions) -> Result<Self, Self::Error> {
+        let invalid = |msg: &str| napi::Error::new(napi::Status::InvalidArg, msg.to_string());
+        let BackoffStrategyOptions {
+            strategy,
+            min_ms: min,
+            max_ms: max,
+            factor,
+            jitter,
+        } = options;
+        if !(min >= 0f64 && min <= max) {
+            return Err(invalid(
+                "minMs must be at least 0 and no greater than maxMs",
+            ));
+        }
+        if jitter.is_some_and(|j| !(0f64..=1f64).contains(&j)) {
+            return Err(invalid("jitter must be between 0 and 1"));
+        }
+        if factor.is_some_and(|f| f.is_nan() || f < 0f64) {
+            return Err(invalid("factor must not be negative"));
+        }
+        Ok(match strategy {
+            BackoffStrategyKind::Exponential => BackoffStrategy::Exponential {
+                min,
+                max,
+                factor: factor.unwrap_or(DEFAULT_EXPONENTIAL_FACTOR),
+                jitter: jitter.unwrap_or(1f64),
+            },
+            BackoffStrategyKind::DecorrelatedJitter => BackoffStrategy::DecorrelatedJitter {
+                min,
+                max,
+                factor: factor.unwrap_or(DEFAULT_DECORRELATED_FACTOR),
+                jitter: jitter.unwrap_or(1f64),
+            },
+            BackoffStrategyKind::Linear => BackoffStrategy::Linear {
+                min,
+                max,
+                step: factor.unwrap_or(min),
+                jitter: jitter.unwrap_or(0f64),
+            },
+            BackoffStrategyKind::Fixed => BackoffStrategy::Fixed {
+                delay: min,
+                max,
+                jitter: jitter.unwrap_or(0f64),
+            },
+        })
+    }
+}
+
+/**
+ * Randomly reduce a duration by up to `jitter` of its value.
+ */
+fn apply_jitter(rng: &mut impl Rng, duration: f64, jitter: f64) -> f64 {
+    duration * (1f64 - jitter * rng.gen::<f64>())
+}
+
+impl BackoffStrategy {
+    /**
+     * Calculate the next backoff duration, given the number of consecutive failures
+     * (including this one) and the previous duration.
+     */
+    pub fn next_duration(&self, failures: u32, last_duration: u32) -> u32 {
+        let mut rng = rand::thread_rng();
+        let exponent = failures.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let last = last_duration as f64;
+        let duration = match *self {
+            BackoffStrategy::Classic {
+                time,
+                exponent,
+                max,
+            } => {
+                let y: f64 = rng.gen::<f64>() + 0.5f64; // generates a float between 0.5 and 1.5
+                ((y * time) + last.powf(exponent)).min(max)
+            }
+            BackoffStrategy::Exponential {
+                min,
+                max,
+                factor,
+                jitter,
+            } => apply_jitter(&mut rng, (min * factor.powi(exponent)).min(max), jitter).max(min),
+            BackoffStrategy::DecorrelatedJitter {
+                min,
+                max,
+                factor,
+                jitter,
+            } => {
+                let upper = (last * factor).max(min);
+                (upper - jitter * rng.gen::<f64>() * (upper - min)).min(max)
+            }
+            BackoffStrategy::Linear {
+                min,
+                max,
+                step,
+                jitter,
+            } => apply_jitter(&mut rng, (min + step * exponent as f64).min(max), jitter).max(min),
+            BackoffStrategy::Fixed { delay, max, jitter } => {
+                apply_jitter(&mut rng, delay.min(max), jitter)
+            }
+        };
+        duration as u32
+    }
+
+    /**
+     * The step used to space out items that would otherwise be retried at the same time.
+     */
+    pub fn spacing(&self) -> f64 {
+        match *self {
+            BackoffStrategy::Classic { time, .. } => time,
+            // Other strategies are jittered, so only need to avoid an exact collision.
+            _ => 1f64,
+        }
+    }
+}
@@ -1,135 +1,520 @@
+pub mod backoff;
+pub mod rate_limiter;
+
+use backoff::{BackoffStrategy, BackoffStrategyOptions};
 use rand::prelude::*;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::VecDeque;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use napi::bindgen_prelude::PromiseRaw;
+use napi::tokio::sync::Notify;
+use napi::Env;
 
 const DEFAULT_BACKOFF_TIME_MAX_MS: f64 = 24f64 * 60f64 * 60f64 * 1000f64;
 const DEFAULT_BACKOFF_POW: f64 = 1.05f64;
 const DEFAULT_BACKOFF_TIME_MS: f64 = 5f64 * 1000f64;
 
-#[napi]
+/**
+ * The persisted state of a `QueueWithBackoff`. The backoff configuration is
+ * not included, so that changes to it take effect on restore.
+ */
+#[derive(Serialize, Deserialize)]
+struct QueueWithBackoffSnapshot {
+    queue: Vec<String>,
+    backoff: BTreeMap<u64, String>,
+    last_backoff_duration: HashMap<String, u32>,
+    #[serde(default)]
+    failure_count: HashMap<String, u32>,
+    #[serde(default)]
+    quarantine: HashMap<String, u64>,
+    #[serde(default)]
+    scheduled: BTreeMap<u64, String>,
+    #[serde(default)]
+    intervals: HashMap<String, u32>,
+}
 
-pub struct QueueWithBackoff {
-    queue: VecDeque<String>,
+#[napi(object)]
+pub struct BackedOffItem {
+    pub item: String,
+    /**
+     * The absolute timestamp (in milliseconds) after which the item will be retried.
+     */
+    pub retry_at: i64,
+    /**
+     * The number of times the item has been backed off since it last succeeded.
+     */
+    pub failure_count: u32,
+    /**
+     * The current backoff duration in milliseconds.
+     */
+    pub backoff_duration: u32,
+}
+
+#[napi(object)]
+pub struct QuarantinedItem {
+    pub item: String,
+    /**
+     * The absolute timestamp (in milliseconds) at which the item was quarantined.
+     */
+    pub quarantined_at: i64,
+    pub failure_count: u32,
+}
+
+#[napi(object)]
+pub struct QueueWithBackoffStats {
+    pub queued: u32,
+    pub backed_off: u32,
+    pub quarantined: u32,
+    /**
+     * The number of items waiting for their poll interval to elapse.
+     */
+    pub scheduled: u32,
+    pub max_failure_count: u32,
+    pub max_backoff_duration: u32,
+    /**
+     * The earliest timestamp (in milliseconds) at which a backed off item will be retried.
+     */
+    pub next_retry_at: Option<i64>,
+}
+
+/**
+ * Where an item currently lives in a `QueueWithBackoff`.
+ */
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Location {
+    /**
+     * In the queue, tagged with the token of its queue entry.
+     */
+    Queued(u64),
+    /**
+     * Backed off until the given absolute timestamp.
+     */
+    Backoff(u64),
+    /**
+     * Waiting for its poll interval, until the given absolute timestamp.
+     */
+    Scheduled(u64),
+    /**
+     * Failed too many times, and will not be returned until released.
+     */
+    Quarantined,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/**
+ * The state of a `QueueWithBackoff`, shared with any pending `nextDue` calls.
+ */
+struct QueueState {
+    /**
+     * Queued values, tagged with a token. Entries whose token no longer
+     * matches the index have been removed, and are skipped when popped.
+     */
+    queue: VecDeque<(u64, String)>,
     /**
      * A map of absolute backoff timestamps mapped to the value.
      */
     backoff: BTreeMap<u64, String>,
+    /**
+     * The location of every value in the queue or backoff.
+     */
+    index: HashMap<String, Location>,
     /**
      * The last duration applied when a value was backed off.
      */
     last_backoff_duration: HashMap<String, u32>,
-    backoff_time: f64,
-    backoff_exponent: f64,
-    backoff_max: f64,
-}
-
-impl Default for QueueWithBackoff {
-    fn default() -> Self {
-        Self::new(
-            DEFAULT_BACKOFF_TIME_MS,
-            DEFAULT_BACKOFF_POW,
-            DEFAULT_BACKOFF_TIME_MAX_MS,
-        )
-    }
+    /**
+     * The number of consecutive times a value has been backed off.
+     */
+    failure_count: HashMap<String, u32>,
+    /**
+     * Values which have been quarantined, mapped to the time they were quarantined.
+     */
+    quarantine: HashMap<String, u64>,
+    /**
+     * The number of consecutive failures after which a value is quarantined.
+     */
+    quarantine_threshold: Option<u32>,
+    /**
+     * A map of absolute timestamps at which values with a poll interval are next due.
+     */
+    scheduled: BTreeMap<u64, String>,
+    /**
+     * The poll interval of values which should not be returned as often as possible.
+     */
+    intervals: HashMap<String, u32>,
+    next_token: u64,
+    /**
+     * The number of removed entries still present in `queue`.
+     */
+    stale_entries: usize,
+    strategy: BackoffStrategy,
+    /**
+     * Incremented to cancel any pending `nextDue` calls.
+     */
+    cancel_generation: u64,
 }
-#[napi]
 
-impl QueueWithBackoff {
-    #[napi(constructor)]
-    pub fn new(backoff_time: f64, backoff_exponent: f64, backoff_max: f64) -> Self {
-        QueueWithBackoff {
+impl QueueState {
+    fn with_backoff_strategy(strategy: BackoffStrategy) -> Self {
+        QueueState {
             queue: VecDeque::new(),
             backoff: BTreeMap::new(),
+            index: HashMap::new(),
             last_backoff_duration: HashMap::new(),
-            backoff_time,
-            backoff_exponent,
-            backoff_max,
+            failure_count: HashMap::new(),
+            quarantine: HashMap::new(),
+            quarantine_threshold: None,
+            scheduled: BTreeMap::new(),
+            intervals: HashMap::new(),
+            next_token: 0,
+            stale_entries: 0,
+            strategy,
+            cancel_generation: 0,
         }
     }
 
-    #[napi]
+    fn from_snapshot(snapshot: String, strategy: BackoffStrategy) -> napi::Result<Self> {
+        let snapshot: QueueWithBackoffSnapshot = serde_json::from_str(&snapshot).map_err(|e| {
+            napi::Error::new(
+                napi::Status::InvalidArg,
+                format!("Could not parse queue snapshot: {}", e),
+            )
+        })?;
+        let mut queue = Self::with_backoff_strategy(strategy);
+        for (item, time) in snapshot.quarantine {
+            queue.index.insert(item.clone(), Location::Quarantined);
+            queue.quarantine.insert(item, time);
+        }
+        for (time, item) in snapshot.backoff {
+            if !queue.index.contains_key(&item) {
+                queue.index.insert(item.clone(), Location::Backoff(time));
+                queue.backoff.insert(time, item);
+            }
+        }
+        for (time, item) in snapshot.scheduled {
+            if !queue.index.contains_key(&item) {
+                queue.index.insert(item.clone(), Location::Scheduled(time));
+                queue.scheduled.insert(time, item);
+            }
+        }
+        for item in snapshot.queue {
+            if !queue.index.contains_key(&item) {
+                queue.enqueue(item);
+            }
+        }
+        queue.last_backoff_duration = snapshot.last_backoff_duration;
+        queue.failure_count = snapshot.failure_count;
+        queue.intervals = snapshot.intervals;
+        Ok(queue)
+    }
+
+    fn enqueue(&mut self, item: String) {
+        let token = self.next_token;
+        self.next_token += 1;
+        self.index.insert(item.clone(), Location::Queued(token));
+        self.queue.push_back((token, item));
+    }
+
+    /**
+     * Remove an item from wherever it is held, returning true if it was present.
+     */
+    fn detach(&mut self, item: &str) -> bool {
+        match self.index.remove(item) {
+            Some(Location::Queued(_)) => {
+                // The queue entry is left in place, and skipped over when popped.
+                self.stale_entries += 1;
+                if self.stale_entries > self.queue.len() / 2 {
+                    self.compact();
+                }
+                true
+            }
+            Some(Location::Backoff(time)) => {
+                self.backoff.remove(&time);
+                true
+            }
+            Some(Location::Scheduled(time)) => {
+                self.scheduled.remove(&time);
+                true
+            }
+            Some(Location::Quarantined) => {
+                self.quarantine.remove(item);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /**
+     * Drop removed entries from the queue.
+     */
+    fn compact(&mut self) {
+        let index = &self.index;
+        self.queue
+            .retain(|(token, item)| index.get(item) == Some(&Location::Queued(*token)));
+        self.stale_entries = 0;
+    }
+
+    pub fn snapshot(&self) -> napi::Result<String> {
+        serde_json::to_string(&QueueWithBackoffSnapshot {
+            queue: self
+                .queue
+                .iter()
+                .filter(|(token, item)| self.index.get(item) == Some(&Location::Queued(*token)))
+                .map(|(_, item)| item.clone())
+                .collect(),
+            backoff: self.backoff.clone(),
+            last_backoff_duration: self.last_backoff_duration.clone(),
+            failure_count: self.failure_count.clone(),
+            quarantine: self.quarantine.clone(),
+            scheduled: self.scheduled.clone(),
+            intervals: self.intervals.clone(),
+        })
+        .map_err(|e| napi::Error::new(napi::Status::GenericFailure, e.to_string()))
+    }
+
+    pub fn set_quarantine_threshold(&mut self, threshold: Option<u32>) {
+        self.quarantine_threshold = threshold;
+    }
+
     pub fn pop(&mut self) -> Option<String> {
-        let start = SystemTime::now();
-        let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let since_the_epoch = now_millis();
 
         // We only need to check this once, as we won't be adding to the backoff queue
         // as often as we pull from it.
         if let Some(item) = self.backoff.first_entry() {
             if *item.key() < since_the_epoch {
                 let v = item.remove();
-                self.queue.push_back(v);
+                self.enqueue(v);
+            }
+        }
+        // Likewise for items which have waited out their poll interval.
+        if let Some(item) = self.scheduled.first_entry() {
+            if *item.key() <= since_the_epoch {
+                let v = item.remove();
+                self.enqueue(v);
             }
         }
 
-        self.queue.pop_front()
+        while let Some((token, item)) = self.queue.pop_front() {
+            if self.index.get(&item) == Some(&Location::Queued(token)) {
+                self.index.remove(&item);
+                return Some(item);
+            }
+            self.stale_entries = self.stale_entries.saturating_sub(1);
+        }
+        None
     }
 
-    #[napi]
     pub fn remove(&mut self, item: String) -> bool {
-        // Remove from the queue
-        if let Ok(index) = self.queue.binary_search(&item) {
-            self.queue.remove(index);
-            return true;
-        } else {
-            // We didn't find the key queued, so let's ensure we delete it
-            // from any backoff.
-            // This is *expensive* but hopefully called rarely.
-            let mut found_key: u64 = 0;
-            for (key, value) in self.backoff.iter() {
-                if *value == item {
-                    found_key = *key;
-                }
-            }
-            if found_key != 0 {
-                self.backoff.remove(&found_key);
-                return true;
-            }
-        }
         // Always remove the duration on removal.
         self.last_backoff_duration.remove(&item);
-        false
+        self.failure_count.remove(&item);
+        self.intervals.remove(&item);
+        self.detach(&item)
+    }
+
+    pub fn contains(&self, item: String) -> bool {
+        self.index.contains_key(&item)
     }
 
-    #[napi]
     pub fn push(&mut self, item: String) {
+        if self.quarantine.contains_key(&item) {
+            return;
+        }
         self.last_backoff_duration.remove(&item);
-        self.queue.push_back(item);
+        self.failure_count.remove(&item);
+        if let Some(interval) = self.intervals.get(&item).copied() {
+            if !matches!(self.index.get(&item), Some(Location::Scheduled(_))) {
+                self.detach(&item);
+                self.schedule(item, now_millis() + interval as u64);
+            }
+            return;
+        }
+        match self.index.get(&item) {
+            Some(Location::Queued(_)) => {}
+            Some(Location::Backoff(_)) | Some(Location::Scheduled(_)) => {
+                self.detach(&item);
+                self.enqueue(item);
+            }
+            _ => self.enqueue(item),
+        }
+    }
+
+    fn schedule(&mut self, item: String, mut time: u64) {
+        while self.scheduled.contains_key(&time) {
+            time += 1;
+        }
+        self.index.insert(item.clone(), Location::Scheduled(time));
+        self.scheduled.insert(time, item);
+    }
+
+    pub fn set_interval(&mut self, item: String, interval: Option<u32>) {
+        match interval {
+            Some(interval) => {
+                // Bring forward an item which is now due sooner than it was scheduled for.
+                let due = now_millis() + interval as u64;
+                if let Some(&Location::Scheduled(time)) = self.index.get(&item) {
+                    if time > due {
+                        self.scheduled.remove(&time);
+                        self.schedule(item.clone(), due);
+                    }
+                }
+                self.intervals.insert(item, interval);
+            }
+            None => {
+                self.intervals.remove(&item);
+                if let Some(&Location::Scheduled(time)) = self.index.get(&item) {
+                    self.scheduled.remove(&time);
+                    self.enqueue(item);
+                }
+            }
+        }
+    }
+
+    pub fn scheduled_at(&self, item: String) -> Option<i64> {
+        match self.index.get(&item) {
+            Some(&Location::Scheduled(time)) => Some(time as i64),
+            _ => None,
+        }
     }
 
-    #[napi]
     pub fn backoff(&mut self, item: String) -> u32 {
-        let last_backoff = (*self.last_backoff_duration.get(&item).unwrap_or(&0)) as f64;
+        let last_backoff = *self.last_backoff_duration.get(&item).unwrap_or(&0);
+        let failures = self.failure_count.entry(item.clone()).or_insert(0);
+        *failures += 1;
 
-        let mut rng = rand::thread_rng();
-        let y: f64 = rng.gen::<f64>() + 0.5f64; // generates a float between 0.5 and 1.1
+        if self.quarantine_threshold.is_some_and(|t| *failures >= t) {
+            self.detach(&item);
+            self.index.insert(item.clone(), Location::Quarantined);
+            self.quarantine.insert(item, now_millis());
+            return 0;
+        }
 
-        let backoff_duration = ((y * self.backoff_time) + last_backoff.powf(self.backoff_exponent))
-            .min(self.backoff_max) as u32;
+        let backoff_duration = self.strategy.next_duration(*failures, last_backoff);
         let backoff_item = item.clone();
         self.last_backoff_duration.insert(item, backoff_duration);
+        // An item can only be in one place at a time.
+        self.detach(&backoff_item);
 
-        let start = SystemTime::now();
-        let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap();
-
-        let mut time = since_the_epoch.as_millis() as u64 + backoff_duration as u64;
+        let mut time = now_millis() + backoff_duration as u64;
 
         // If the backoff queue contains this time (likely)
         // then we want to increase the backoff time slightly
         // to allow for it.
+        let mut rng = rand::thread_rng();
         let incr: f64 = (rng.gen::<f64>() * 2f64) + 2f64;
         while self.backoff.contains_key(&time) {
-            time += (incr * self.backoff_time) as u64;
+            time += (incr * self.strategy.spacing()) as u64;
         }
 
+        self.index
+            .insert(backoff_item.clone(), Location::Backoff(time));
         self.backoff.insert(time, backoff_item);
         backoff_duration
     }
 
-    #[napi]
     pub fn length(&self) -> u32 {
-        self.queue.len() as u32
+        self.queue.len().saturating_sub(self.stale_entries) as u32
+    }
+
+    /**
+     * The time at which the next backed off or scheduled item will be due.
+     */
+    fn next_timed_at(&self) -> Option<u64> {
+        let next_backoff = self.backoff.keys().next().copied();
+        let next_scheduled = self.scheduled.keys().next().copied();
+        next_backoff.into_iter().chain(next_scheduled).min()
+    }
+
+    fn backed_off_item(&self, time: u64, item: &str) -> BackedOffItem {
+        BackedOffItem {
+            item: item.to_string(),
+            retry_at: time as i64,
+            failure_count: self.failure_count.get(item).copied().unwrap_or(0),
+            backoff_duration: self.last_backoff_duration.get(item).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn backed_off_items(&self) -> Vec<BackedOffItem> {
+        self.backoff
+            .iter()
+            .map(|(time, item)| self.backed_off_item(*time, item))
+            .collect()
+    }
+
+    pub fn backoff_status(&self, item: String) -> Option<BackedOffItem> {
+        match self.index.get(&item) {
+            Some(&Location::Backoff(time)) => Some(self.backed_off_item(time, &item)),
+            _ => None,
+        }
+    }
+
+    pub fn is_quarantined(&self, item: String) -> bool {
+        self.quarantine.contains_key(&item)
+    }
+
+    pub fn quarantined_items(&self) -> Vec<QuarantinedItem> {
+        let mut items: Vec<QuarantinedItem> = self
+            .quarantine
+            .iter()
+            .map(|(item, time)| QuarantinedItem {
+                item: item.clone(),
+                quarantined_at: *time as i64,
+                failure_count: self.failure_count.get(item).copied().unwrap_or(0),
+            })
+            .collect();
+        items.sort_by_key(|i| i.quarantined_at);
+        items
+    }
+
+    pub fn release_quarantined(&mut self, item: String) -> bool {
+        if self.quarantine.remove(&item).is_none() {
+            return false;
+        }
+        self.index.remove(&item);
+        self.push(item);
+        true
+    }
+
+    pub fn purge_quarantined(&mut self) -> Vec<String> {
+        let items: Vec<String> = self.quarantine.drain().map(|(item, _)| item).collect();
+        for item in items.iter() {
+            self.index.remove(item);
+            self.failure_count.remove(item);
+            self.last_backoff_duration.remove(item);
+        }
+        items
+    }
+
+    pub fn stats(&self) -> QueueWithBackoffStats {
+        let backed_off = self.backoff.values();
+        QueueWithBackoffStats {
+            queued: self.length(),
+            backed_off: self.backoff.len() as u32,
+            quarantined: self.quarantine.len() as u32,
+            scheduled: self.scheduled.len() as u32,
+            max_failure_count: backed_off
+                .clone()
+                .filter_map(|item| self.failure_count.get(item))
+                .copied()
+                .max()
+                .unwrap_or(0),
+            max_backoff_duration: backed_off
+                .filter_map(|item| self.last_backoff_duration.get(item))
+                .copied()
+                .max()
+                .unwrap_or(0),
+            next_retry_at: self.backoff.keys().next().map(|t| *t as i64),
+        }
     }
 
     fn shuffle(&mut self) {
@@ -137,12 +522,301 @@ impl QueueWithBackoff {
         self.queue.make_contiguous().shuffle(&mut rng);
     }
 
-    #[napi]
     pub fn populate(&mut self, values: Vec<String>) {
-        // This assumes an empty queue.
         for v in values {
-            self.queue.push_back(v);
+            if !self.index.contains_key(&v) {
+                self.enqueue(v);
+            }
         }
         self.shuffle();
     }
 }
+
+#[napi]
+pub struct QueueWithBackoff {
+    state: Arc<Mutex<QueueState>>,
+    /**
+     * Wakes any pending `nextDue` calls when an item may have become available.
+     */
+    notify: Arc<Notify>,
+}
+
+impl Default for QueueWithBackoff {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_BACKOFF_TIME_MS,
+            DEFAULT_BACKOFF_POW,
+            DEFAULT_BACKOFF_TIME_MAX_MS,
+        )
+    }
+}
+
+impl QueueWithBackoff {
+    fn from_state(state: QueueState) -> Self {
+        QueueWithBackoff {
+            state: Arc::new(Mutex::new(state)),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap()
+    }
+}
+
+#[napi]
+
+impl QueueWithBackoff {
+    #[napi(constructor)]
+    pub fn new(backoff_time: f64, backoff_exponent: f64, backoff_max: f64) -> Self {
+        Self::from_state(QueueState::with_backoff_strategy(
+            BackoffStrategy::Classic {
+                time: backoff_time,
+                exponent: backoff_exponent,
+                max: backoff_max,
+            },
+        ))
+    }
+
+    /**
+     * Create a queue which uses one of the alternative backoff strategies.
+     */
+    #[napi(factory)]
+    pub fn with_strategy(options: BackoffStrategyOptions) -> napi::Result<Self> {
+        Ok(Self::from_state(QueueState::with_backoff_strategy(
+            options.try_into()?,
+        )))
+    }
+
+    /**
+     * Restore a queue from a snapshot previously created with `snapshot`.
+     */
+    #[napi(factory)]
+    pub fn restore(
+        snapshot: String,
+        backoff_time: f64,
+        backoff_exponent: f64,
+        backoff_max: f64,
+    ) -> napi::Result<Self> {
+        QueueState::from_snapshot(
+            snapshot,
+            BackoffStrategy::Classic {
+                time: backoff_time,
+                exponent: backoff_exponent,
+                max: backoff_max,
+            },
+        )
+        .map(Self::from_state)
+    }
+
+    /**
+     * Restore a queue from a snapshot, using one of the alternative backoff strategies.
+     */
+    #[napi(factory)]
+    pub fn restore_with_strategy(
+        snapshot: String,
+        options: BackoffStrategyOptions,
+    ) -> napi::Result<Self> {
+        QueueState::from_snapshot(snapshot, options.try_into()?).map(Self::from_state)
+    }
+
+    /**
+     * Serialise the queue, backoff state and last backoff durations to JSON.
+     */
+    #[napi]
+    pub fn snapshot(&self) -> napi::Result<String> {
+        self.state().snapshot()
+    }
+
+    /**
+     * Set the number of consecutive failures after which an item is quarantined,
+     * or `null` to never quarantine items.
+     */
+    #[napi]
+    pub fn set_quarantine_threshold(&self, threshold: Option<u32>) {
+        self.state().set_quarantine_threshold(threshold);
+    }
+
+    #[napi]
+    pub fn pop(&self) -> Option<String> {
+        self.state().pop()
+    }
+
+    /**
+     * Set how often an item should be returned, in milliseconds. Once pushed back
+     * after being popped, the item will not be returned again until the interval has
+     * passed. Pass `null` to return the item as often as possible.
+     */
+    #[napi]
+    pub fn set_interval(&self, item: String, interval_ms: Option<u32>) {
+        self.state().set_interval(item, interval_ms);
+        self.notify.notify_waiters();
+    }
+
+    /**
+     * Get the absolute timestamp (in milliseconds) at which an item waiting for its
+     * poll interval will next be due.
+     */
+    #[napi]
+    pub fn scheduled_at(&self, item: String) -> Option<i64> {
+        self.state().scheduled_at(item)
+    }
+
+    /**
+     * Wait for the next item to become due, either by being pushed, by leaving
+     * backoff or by reaching its poll interval, and pop it. Resolves with `null` if `cancelNextDue` is called first.
+     */
+    #[napi(ts_return_type = "Promise<string | null>")]
+    pub fn next_due<'env>(&self, env: &'env Env) -> napi::Result<PromiseRaw<'env, Option<String>>> {
+        let state = self.state.clone();
+        let notify = self.notify.clone();
+        let generation = self.state().cancel_generation;
+        env.spawn_future(async move {
+            loop {
+                // Register for notifications before checking the queue, so that
+                // a push between the check and the wait is not missed.
+                let notified = notify.notified();
+                napi::tokio::pin!(notified);
+                notified.as_mut().enable();
+
+                let next_timed_at = {
+                    let mut state = state.lock().unwrap();
+                    if state.cancel_generation != generation {
+                        return Ok(None);
+                    }
+                    if let Some(item) = state.pop() {
+                        return Ok(Some(item));
+                    }
+                    state.next_timed_at()
+                };
+
+                match next_timed_at {
+                    Some(time) => {
+                        let wait = Duration::from_millis(time.saturating_sub(now_millis()) + 1);
+                        napi::tokio::select! {
+                            _ = &mut notified => {},
+                            _ = napi::tokio::time::sleep(wait) => {},
+                        }
+                    }
+                    None => notified.await,
+                }
+            }
+        })
+    }
+
+    /**
+     * Resolve any pending `nextDue` calls with `null`.
+     */
+    #[napi]
+    pub fn cancel_next_due(&self) {
+        self.state().cancel_generation += 1;
+        self.notify.notify_waiters();
+    }
+
+    /**
+     * Remove an item from the queue, backoff or quarantine. Returns true if the item was present.
+     */
+    #[napi]
+    pub fn remove(&self, item: String) -> bool {
+        self.state().remove(item)
+    }
+
+    /**
+     * Check if an item is currently queued, backed off or quarantined.
+     */
+    #[napi]
+    pub fn contains(&self, item: String) -> bool {
+        self.state().contains(item)
+    }
+
+    /**
+     * Queue an item, resetting any backoff. Items with an interval are instead
+     * scheduled to be due once the interval has passed. Items that are already
+     * queued keep their position, and quarantined items must be released with
+     * `releaseQuarantined`.
+     */
+    #[napi]
+    pub fn push(&self, item: String) {
+        self.state().push(item);
+        self.notify.notify_waiters();
+    }
+
+    /**
+     * Back off an item after a failure, returning the backoff duration in milliseconds.
+     * If the item has reached the quarantine threshold it is quarantined instead, and
+     * 0 is returned.
+     */
+    #[napi]
+    pub fn backoff(&self, item: String) -> u32 {
+        let duration = self.state().backoff(item);
+        // The item may now be due sooner than anything else in backoff.
+        self.notify.notify_waiters();
+        duration
+    }
+
+    #[napi]
+    pub fn length(&self) -> u32 {
+        self.state().length()
+    }
+
+    /**
+     * List all backed off items, ordered by the time they will next be retried.
+     */
+    #[napi]
+    pub fn backed_off_items(&self) -> Vec<BackedOffItem> {
+        self.state().backed_off_items()
+    }
+
+    /**
+     * Get the backoff state of a single item, if it is currently backed off.
+     */
+    #[napi]
+    pub fn backoff_status(&self, item: String) -> Option<BackedOffItem> {
+        self.state().backoff_status(item)
+    }
+
+    #[napi]
+    pub fn is_quarantined(&self, item: String) -> bool {
+        self.state().is_quarantined(item)
+    }
+
+    /**
+     * List all quarantined items, oldest first.
+     */
+    #[napi]
+    pub fn quarantined_items(&self) -> Vec<QuarantinedItem> {
+        self.state().quarantined_items()
+    }
+
+    /**
+     * Release an item from quarantine, queueing it again with its failures reset.
+     * Returns false if the item was not quarantined.
+     */
+    #[napi]
+    pub fn release_quarantined(&self, item: String) -> bool {
+        let released = self.state().release_quarantined(item);
+        if released {
+            self.notify.notify_waiters();
+        }
+        released
+    }
+
+    /**
+     * Remove all quarantined items from the queue entirely, returning them.
+     */
+    #[napi]
+    pub fn purge_quarantined(&self) -> Vec<String> {
+        self.state().purge_quarantined()
+    }
+
+    #[napi]
+    pub fn stats(&self) -> QueueWithBackoffStats {
+        self.state().stats()
+    }
+
+    #[napi]
+    pub fn populate(&self, values: Vec<String>) {
+        self.state().populate(values);
+        self.notify.notify_waiters();
+    }
+}
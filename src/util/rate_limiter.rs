@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use napi::bindgen_prelude::PromiseRaw;
+use napi::Env;
+
+const DEFAULT_IDLE_TIMEOUT_MS: f64 = 10f64 * 60f64 * 1000f64;
+
+struct Bucket {
+    /**
+     * The number of available tokens. This may be negative if tokens have been
+     * reserved by callers waiting in `acquire`.
+     */
+    tokens: f64,
+    last_refill: Instant,
+}
+
+struct RateLimiterState {
+    buckets: HashMap<String, Bucket>,
+    burst: f64,
+    /**
+     * Tokens added to each bucket per second.
+     */
+    refill_rate: f64,
+    idle_timeout: Duration,
+    last_eviction: Instant,
+}
+
+impl RateLimiterState {
+    /**
+     * Get the bucket for a key, topped up for the time elapsed since it was last used.
+     */
+    fn bucket(&mut self, key: String, now: Instant) -> &mut Bucket {
+        if now.duration_since(self.last_eviction) >= self.idle_timeout {
+            self.evict_idle(now);
+        }
+        let (burst, refill_rate) = (self.burst, self.refill_rate);
+        let bucket = self.buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            last_refill: now,
+        });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_rate).min(burst);
+        bucket.last_refill = now;
+        bucket
+    }
+
+    /**
+     * Drop buckets which have been idle long enough to have refilled completely,
+     * as they are indistinguishable from a new bucket.
+     */
+    fn evict_idle(&mut self, now: Instant) -> u32 {
+        let before = self.buckets.len();
+        let (burst, refill_rate, idle_timeout) = (self.burst, self.refill_rate, self.idle_timeout);
+        self.buckets.retain(|_, bucket| {
+            let idle = now.duration_since(bucket.last_refill);
+            idle < idle_timeout || bucket.tokens + idle.as_secs_f64() * refill_rate < burst
+        });
+        self.last_eviction = now;
+        (before - self.buckets.len()) as u32
+    }
+}
+
+/**
+ * A rate limiter with a token bucket for each key.
+ */
+#[napi]
+pub struct RateLimiter {
+    state: Arc<Mutex<RateLimiterState>>,
+}
+
+impl RateLimiter {
+    fn state(&self) -> MutexGuard<'_, RateLimiterState> {
+        self.state.lock().unwrap()
+    }
+}
+
+fn validate_tokens(tokens: Option<f64>) -> napi::Result<f64> {
+    let tokens = tokens.unwrap_or(1f64);
+    if !tokens.is_finite() || tokens <= 0f64 {
+        return Err(napi::Error::new(
+            napi::Status::InvalidArg,
+            "tokens must be a finite number greater than 0",
+        ));
+    }
+    Ok(tokens)
+}
+
+#[napi]
+impl RateLimiter {
+    /**
+     * @param burst The maximum number of tokens a key may hold, and so the largest burst allowed.
+     * @param refillPerSecond The number of tokens returned to each key every second.
+     * @param idleTimeoutMs How long a key must be unused before it may be evicted. Defaults to 10 minutes.
+     */
+    #[napi(constructor)]
+    pub fn new(
+        burst: f64,
+        refill_per_second: f64,
+        idle_timeout_ms: Option<f64>,
+    ) -> napi::Result<Self> {
+        if burst.is_nan() || burst < 1f64 {
+            return Err(napi::Error::new(
+                napi::Status::InvalidArg,
+                "burst must be at least 1",
+            ));
+        }
+        if refill_per_second.is_nan() || refill_per_second <= 0f64 {
+            return Err(napi::Error::new(
+                napi::Status::InvalidArg,
+                "refillPerSecond must be greater than 0",
+            ));
+        }
+        let idle_timeout = idle_timeout_ms.unwrap_or(DEFAULT_IDLE_TIMEOUT_MS).max(0f64);
+        Ok(RateLimiter {
+            state: Arc::new(Mutex::new(RateLimiterState {
+                buckets: HashMap::new(),
+                burst,
+                refill_rate: refill_per_second,
+                idle_timeout: Duration::from_millis(idle_timeout as u64),
+                last_eviction: Instant::now(),
+            })),
+        })
+    }
+
+    /**
+     * Take tokens for a key if they are available. Returns false, without taking
+     * any tokens, if the key is currently limited.
+     */
+    #[napi]
+    pub fn try_acquire(&self, key: String, tokens: Option<f64>) -> napi::Result<bool> {
+        let tokens = validate_tokens(tokens)?;
+        let mut state = self.state();
+        let bucket = state.bucket(key, Instant::now());
+        if bucket.tokens >= tokens {
+            bucket.tokens -= tokens;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /**
+     * Take tokens for a key, waiting until they are available. Callers are
+     * served in the order they called `acquire`.
+     */
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn acquire<'env>(
+        &self,
+        env: &'env Env,
+        key: String,
+        tokens: Option<f64>,
+    ) -> napi::Result<PromiseRaw<'env, ()>> {
+        let tokens = validate_tokens(tokens)?;
+        let wait = {
+            let mut state = self.state();
+            let refill_rate = state.refill_rate;
+            let bucket = state.bucket(key, Instant::now());
+            let wait =
+                Duration::try_from_secs_f64((tokens - bucket.tokens).max(0f64) / refill_rate)
+                    .map_err(|_| {
+                        napi::Error::new(
+                            napi::Status::InvalidArg,
+                            "tokens would take too long to become available",
+                        )
+                    })?;
+            // Reserve the tokens now, and wait for the bucket to refill to cover them.
+            bucket.tokens -= tokens;
+            wait
+        };
+        env.spawn_future(async move {
+            if !wait.is_zero() {
+                napi::tokio::time::sleep(wait).await;
+            }
+            Ok(())
+        })
+    }
+
+    /**
+     * The number of tokens currently available for a key.
+     */
+    #[napi]
+    pub fn available(&self, key: String) -> f64 {
+        let mut state = self.state();
+        if !state.buckets.contains_key(&key) {
+            return state.burst;
+        }
+        state.bucket(key, Instant::now()).tokens.max(0f64)
+    }
+
+    /**
+     * Evict keys which have been idle for longer than the idle timeout, returning
+     * the number evicted. This also happens automatically as the limiter is used.
+     */
+    #[napi]
+    pub fn evict_idle(&self) -> u32 {
+        self.state().evict_idle(Instant::now())
+    }
+
+    /**
+     * The number of keys currently tracked.
+     */
+    #[napi]
+    pub fn size(&self) -> u32 {
+        self.state().buckets.len() as u32
+    }
+}
@@ -0,0 +1,553 @@
+import { describe, it, expect } from "vitest";
+import {
+  BackoffStrategyKind,
+  BackoffStrategyOptions,
+  QueueWithBackoff,
+} from "../src/libRs";
+
+const BACKOFF_TIME_MS = 10;
+const BACKOFF_MAX_MS = 100;
+
+function createQueue() {
+  return new QueueWithBackoff(BACKOFF_TIME_MS, 1.05, BACKOFF_MAX_MS);
+}
+
+function popAll(queue: QueueWithBackoff) {
+  const items = [];
+  for (let item = queue.pop(); item !== null; item = queue.pop()) {
+    items.push(item);
+  }
+  return items;
+}
+
+describe("QueueWithBackoff", () => {
+  it("will pop items in the order they were pushed", () => {
+    const queue = createQueue();
+    queue.push("a");
+    queue.push("b");
+    queue.push("c");
+    expect(queue.length()).toBe(3);
+    expect(popAll(queue)).toEqual(["a", "b", "c"]);
+    expect(queue.length()).toBe(0);
+  });
+
+  it("will not queue an item twice", () => {
+    const queue = createQueue();
+    queue.push("a");
+    queue.push("b");
+    queue.push("a");
+    queue.populate(["a", "b"]);
+    expect(queue.length()).toBe(2);
+    expect(popAll(queue).sort()).toEqual(["a", "b"]);
+  });
+
+  it("will remove queued items", () => {
+    const queue = createQueue();
+    queue.push("a");
+    queue.push("b");
+    queue.push("c");
+    expect(queue.remove("b")).toBe(true);
+    expect(queue.remove("b")).toBe(false);
+    expect(queue.contains("b")).toBe(false);
+    expect(queue.contains("a")).toBe(true);
+    expect(queue.length()).toBe(2);
+    expect(popAll(queue)).toEqual(["a", "c"]);
+  });
+
+  it("will remove items from an unsorted queue", () => {
+    const queue = createQueue();
+    for (const item of ["e", "d", "c", "b", "a"]) {
+      queue.push(item);
+    }
+    expect(queue.remove("b")).toBe(true);
+    expect(queue.remove("e")).toBe(true);
+    expect(queue.contains("b")).toBe(false);
+    expect(queue.contains("e")).toBe(false);
+    expect(popAll(queue)).toEqual(["d", "c", "a"]);
+  });
+
+  it("will queue a removed item again at the back", () => {
+    const queue = createQueue();
+    queue.push("a");
+    queue.push("b");
+    queue.remove("a");
+    queue.push("a");
+    expect(queue.length()).toBe(2);
+    expect(popAll(queue)).toEqual(["b", "a"]);
+    expect(queue.length()).toBe(0);
+  });
+
+  it("will keep an accurate length as removed items are compacted", () => {
+    const queue = createQueue();
+    const items = [...Array(10).keys()].map((i) => `item-${i}`);
+    items.forEach((item) => queue.push(item));
+    for (const item of items.slice(0, 8)) {
+      queue.remove(item);
+    }
+    expect(queue.length()).toBe(2);
+    queue.push("item-0");
+    expect(queue.length()).toBe(3);
+    expect(popAll(queue)).toEqual(["item-8", "item-9", "item-0"]);
+    expect(queue.length()).toBe(0);
+  });
+
+  it("will remove items from backoff", () => {
+    const queue = createQueue();
+    queue.push("a");
+    queue.pop();
+    queue.backoff("a");
+    expect(queue.contains("a")).toBe(true);
+    expect(queue.length()).toBe(0);
+    expect(queue.remove("a")).toBe(true);
+    expect(queue.contains("a")).toBe(false);
+    expect(queue.backoffStatus("a")).toBeNull();
+    expect(queue.backedOffItems()).toEqual([]);
+  });
+
+  it("will move a backed off item back into the queue when pushed", () => {
+    const queue = createQueue();
+    queue.backoff("a");
+    queue.push("a");
+    expect(queue.backoffStatus("a")).toBeNull();
+    expect(queue.length()).toBe(1);
+    expect(popAll(queue)).toEqual(["a"]);
+  });
+
+  it("will return backed off items once their backoff has passed", async () => {
+    const queue = createQueue();
+    queue.backoff("a");
+    expect(queue.pop()).toBeNull();
+    await new Promise((r) => setTimeout(r, BACKOFF_MAX_MS + 10));
+    expect(queue.pop()).toBe("a");
+  });
+
+  describe("stats", () => {
+    it("will report backed off items in retry order", () => {
+      const queue = createQueue();
+      queue.backoff("a");
+      queue.backoff("b");
+      queue.backoff("b");
+      const items = queue.backedOffItems();
+      expect(items.map((i) => i.item).sort()).toEqual(["a", "b"]);
+      expect(items[0].retryAt).toBeLessThanOrEqual(items[1].retryAt);
+      expect(queue.backoffStatus("b")).toMatchObject({
+        item: "b",
+        failureCount: 2,
+      });
+      expect(queue.backoffStatus("b")?.retryAt).toBeGreaterThan(Date.now());
+    });
+
+    it("will reset the failure count when an item is pushed", () => {
+      const queue = createQueue();
+      queue.backoff("a");
+      queue.backoff("a");
+      queue.push("a");
+      queue.pop();
+      queue.backoff("a");
+      expect(queue.backoffStatus("a")?.failureCount).toBe(1);
+    });
+
+    it("will count items in each state", () => {
+      const queue = createQueue();
+      queue.setQuarantineThreshold(3);
+      queue.push("queued");
+      queue.backoff("a");
+      const duration = queue.backoff("b");
+      queue.backoff("b");
+      queue.backoff("quarantined");
+      queue.backoff("quarantined");
+      queue.backoff("quarantined");
+      queue.setInterval("scheduled", 60000);
+      queue.push("scheduled");
+
+      const stats = queue.stats();
+      expect(stats).toMatchObject({
+        queued: 1,
+        backedOff: 2,
+        quarantined: 1,
+        scheduled: 1,
+        maxFailureCount: 2,
+      });
+      expect(stats.maxBackoffDuration).toBe(
+        Math.max(...queue.backedOffItems().map((i) => i.backoffDuration)),
+      );
+      expect(stats.maxBackoffDuration).toBeGreaterThanOrEqual(duration);
+      expect(stats.nextRetryAt).toBe(queue.backedOffItems()[0].retryAt);
+    });
+
+    it("will not report a retry time when nothing is backed off", () => {
+      const queue = createQueue();
+      queue.push("a");
+      expect(queue.stats()).toEqual({
+        queued: 1,
+        backedOff: 0,
+        quarantined: 0,
+        scheduled: 0,
+        maxFailureCount: 0,
+        maxBackoffDuration: 0,
+      });
+    });
+  });
+
+  describe("backoff strategies", () => {
+    function durations(options: BackoffStrategyOptions, count: number) {
+      const queue = QueueWithBackoff.withStrategy(options);
+      return [...Array(count)].map(() => queue.backoff("a"));
+    }
+
+    it("will use the same duration for a fixed strategy", () => {
+      expect(
+        durations(
+          { strategy: BackoffStrategyKind.Fixed, minMs: 50, maxMs: 100 },
+          3,
+        ),
+      ).toEqual([50, 50, 50]);
+    });
+
+    it("will grow linearly up to the maximum", () => {
+      expect(
+        durations(
+          {
+            strategy: BackoffStrategyKind.Linear,
+            minMs: 100,
+            maxMs: 350,
+            factor: 100,
+          },
+          4,
+        ),
+      ).toEqual([100, 200, 300, 350]);
+    });
+
+    it("will grow exponentially without jitter", () => {
+      expect(
+        durations(
+          {
+            strategy: BackoffStrategyKind.Exponential,
+            minMs: 100,
+            maxMs: 1000,
+            jitter: 0,
+          },
+          5,
+        ),
+      ).toEqual([100, 200, 400, 800, 1000]);
+    });
+
+    it("will keep jittered durations between the minimum and maximum", () => {
+      for (const strategy of [
+        BackoffStrategyKind.Exponential,
+        BackoffStrategyKind.DecorrelatedJitter,
+      ]) {
+        for (const duration of durations(
+          { strategy, minMs: 100, maxMs: 1000 },
+          20,
+        )) {
+          expect(duration).toBeGreaterThanOrEqual(100);
+          expect(duration).toBeLessThanOrEqual(1000);
+        }
+      }
+    });
+
+    it("will honour the jitter of a decorrelated jitter strategy", () => {
+      expect(
+        durations(
+          {
+            strategy: BackoffStrategyKind.DecorrelatedJitter,
+            minMs: 100,
+            maxMs: 1000,
+            factor: 3,
+            jitter: 0,
+          },
+          4,
+        ),
+      ).toEqual([100, 300, 900, 1000]);
+    });
+
+    it("will reject invalid options", () => {
+      expect(() =>
+        QueueWithBackoff.withStrategy({
+          strategy: BackoffStrategyKind.Fixed,
+          minMs: 100,
+          maxMs: 50,
+        }),
+      ).toThrow("minMs must be at least 0 and no greater than maxMs");
+      expect(() =>
+        QueueWithBackoff.withStrategy({
+          strategy: BackoffStrategyKind.DecorrelatedJitter,
+          minMs: 100,
+          maxMs: 1000,
+          jitter: 2,
+        }),
+      ).toThrow("jitter must be between 0 and 1");
+    });
+  });
+
+  describe("quarantine", () => {
+    function quarantinedQueue() {
+      const queue = createQueue();
+      queue.setQuarantineThreshold(2);
+      expect(queue.backoff("a")).toBeGreaterThan(0);
+      expect(queue.backoff("a")).toBe(0);
+      return queue;
+    }
+
+    it("will quarantine items after repeated failures", () => {
+      const queue = quarantinedQueue();
+      expect(queue.isQuarantined("a")).toBe(true);
+      expect(queue.contains("a")).toBe(true);
+      expect(queue.backoffStatus("a")).toBeNull();
+      expect(queue.quarantinedItems()).toMatchObject([
+        { item: "a", failureCount: 2 },
+      ]);
+      expect(queue.quarantinedItems()[0].quarantinedAt).toBeLessThanOrEqual(
+        Date.now(),
+      );
+    });
+
+    it("will not quarantine items without a threshold", () => {
+      const queue = createQueue();
+      for (let i = 0; i < 10; i++) {
+        queue.backoff("a");
+      }
+      expect(queue.isQuarantined("a")).toBe(false);
+    });
+
+    it("will not queue quarantined items when pushed", () => {
+      const queue = quarantinedQueue();
+      queue.push("a");
+      expect(queue.length()).toBe(0);
+      expect(queue.isQuarantined("a")).toBe(true);
+    });
+
+    it("will queue released items with their failures reset", () => {
+      const queue = quarantinedQueue();
+      expect(queue.releaseQuarantined("a")).toBe(true);
+      expect(queue.releaseQuarantined("a")).toBe(false);
+      expect(queue.isQuarantined("a")).toBe(false);
+      expect(queue.length()).toBe(1);
+      expect(queue.pop()).toBe("a");
+      expect(queue.backoff("a")).toBeGreaterThan(0);
+      expect(queue.backoffStatus("a")?.failureCount).toBe(1);
+    });
+
+    it("will purge quarantined items", () => {
+      const queue = quarantinedQueue();
+      queue.backoff("b");
+      queue.backoff("b");
+      queue.backoff("c");
+      expect(queue.purgeQuarantined().sort()).toEqual(["a", "b"]);
+      expect(queue.contains("a")).toBe(false);
+      expect(queue.contains("c")).toBe(true);
+      expect(queue.quarantinedItems()).toEqual([]);
+      queue.push("a");
+      expect(queue.pop()).toBe("a");
+    });
+
+    it("will remove quarantined items", () => {
+      const queue = quarantinedQueue();
+      expect(queue.remove("a")).toBe(true);
+      expect(queue.isQuarantined("a")).toBe(false);
+      expect(queue.contains("a")).toBe(false);
+    });
+  });
+
+  describe("setInterval", () => {
+    it("will schedule items with an interval when pushed", () => {
+      const queue = createQueue();
+      queue.setInterval("a", 60000);
+      queue.push("b");
+      const before = Date.now();
+      queue.push("a");
+      expect(queue.length()).toBe(1);
+      expect(queue.contains("a")).toBe(true);
+      expect(queue.scheduledAt("a")).toBeGreaterThanOrEqual(before + 60000);
+      expect(popAll(queue)).toEqual(["b"]);
+    });
+
+    it("will return scheduled items once their interval has passed", async () => {
+      const queue = createQueue();
+      queue.setInterval("a", 20);
+      queue.push("a");
+      expect(queue.pop()).toBeNull();
+      await new Promise((r) => setTimeout(r, 30));
+      expect(queue.pop()).toBe("a");
+      expect(queue.scheduledAt("a")).toBeNull();
+    });
+
+    it("will keep the scheduled time when pushed again", () => {
+      const queue = createQueue();
+      queue.setInterval("a", 60000);
+      queue.push("a");
+      const scheduledAt = queue.scheduledAt("a");
+      queue.push("a");
+      expect(queue.scheduledAt("a")).toBe(scheduledAt);
+    });
+
+    it("will bring forward items when the interval is shortened", () => {
+      const queue = createQueue();
+      queue.setInterval("a", 60000);
+      queue.push("a");
+      queue.setInterval("a", 1000);
+      expect(queue.scheduledAt("a")).toBeLessThanOrEqual(Date.now() + 1000);
+      queue.setInterval("a", 60000);
+      expect(queue.scheduledAt("a")).toBeLessThanOrEqual(Date.now() + 1000);
+    });
+
+    it("will queue scheduled items when the interval is cleared", () => {
+      const queue = createQueue();
+      queue.setInterval("a", 60000);
+      queue.push("a");
+      queue.setInterval("a", null);
+      expect(queue.scheduledAt("a")).toBeNull();
+      expect(popAll(queue)).toEqual(["a"]);
+      queue.push("a");
+      expect(queue.length()).toBe(1);
+    });
+
+    it("will forget the interval of removed items", () => {
+      const queue = createQueue();
+      queue.setInterval("a", 60000);
+      queue.push("a");
+      expect(queue.remove("a")).toBe(true);
+      expect(queue.scheduledAt("a")).toBeNull();
+      queue.push("a");
+      expect(queue.length()).toBe(1);
+    });
+  });
+
+  describe("nextDue", () => {
+    it("will resolve immediately with a queued item", async () => {
+      const queue = createQueue();
+      queue.push("a");
+      expect(await queue.nextDue()).toBe("a");
+      expect(queue.length()).toBe(0);
+    });
+
+    it("will resolve when an item is pushed", async () => {
+      const queue = createQueue();
+      const due = queue.nextDue();
+      await new Promise((r) => setTimeout(r, 10));
+      queue.push("a");
+      expect(await due).toBe("a");
+    });
+
+    it("will resolve when an item leaves backoff", async () => {
+      const queue = createQueue();
+      queue.backoff("a");
+      const retryAt = queue.backoffStatus("a")!.retryAt;
+      expect(await queue.nextDue()).toBe("a");
+      expect(Date.now()).toBeGreaterThanOrEqual(retryAt);
+    });
+
+    it("will resolve when a scheduled item becomes due", async () => {
+      const queue = createQueue();
+      queue.setInterval("a", 50);
+      queue.push("a");
+      const scheduledAt = queue.scheduledAt("a")!;
+      expect(await queue.nextDue()).toBe("a");
+      expect(Date.now()).toBeGreaterThanOrEqual(scheduledAt);
+    });
+
+    it("will wake for an item backed off while waiting", async () => {
+      const queue = createQueue();
+      queue.setInterval("a", 60000);
+      queue.push("a");
+      const due = queue.nextDue();
+      await new Promise((r) => setTimeout(r, 10));
+      queue.backoff("b");
+      expect(await due).toBe("b");
+    });
+
+    it("will resolve with null when cancelled", async () => {
+      const queue = createQueue();
+      const first = queue.nextDue();
+      const second = queue.nextDue();
+      queue.cancelNextDue();
+      expect(await first).toBeNull();
+      expect(await second).toBeNull();
+      // Calls made after cancelling are unaffected.
+      const third = queue.nextDue();
+      queue.push("a");
+      expect(await third).toBe("a");
+    });
+  });
+
+  describe("snapshot", () => {
+    it("will restore queued items in order", () => {
+      const queue = createQueue();
+      queue.push("a");
+      queue.push("b");
+      queue.push("c");
+      queue.remove("b");
+      const restored = QueueWithBackoff.restore(
+        queue.snapshot(),
+        BACKOFF_TIME_MS,
+        1.05,
+        BACKOFF_MAX_MS,
+      );
+      expect(restored.length()).toBe(2);
+      expect(popAll(restored)).toEqual(["a", "c"]);
+    });
+
+    it("will restore backed off, quarantined and scheduled items", () => {
+      const queue = createQueue();
+      queue.setQuarantineThreshold(2);
+      queue.backoff("backoff");
+      queue.backoff("quarantined");
+      queue.backoff("quarantined");
+      queue.setInterval("scheduled", 60000);
+      queue.push("scheduled");
+
+      const restored = QueueWithBackoff.restore(
+        queue.snapshot(),
+        BACKOFF_TIME_MS,
+        1.05,
+        BACKOFF_MAX_MS,
+      );
+      expect(restored.backoffStatus("backoff")).toEqual(
+        queue.backoffStatus("backoff"),
+      );
+      expect(restored.quarantinedItems()).toEqual(queue.quarantinedItems());
+      expect(restored.scheduledAt("scheduled")).toBe(
+        queue.scheduledAt("scheduled"),
+      );
+      expect(JSON.parse(restored.snapshot())).toEqual(
+        JSON.parse(queue.snapshot()),
+      );
+    });
+
+    it("will keep the interval of restored items", () => {
+      const queue = createQueue();
+      queue.setInterval("a", 60000);
+      const restored = QueueWithBackoff.restoreWithStrategy(queue.snapshot(), {
+        strategy: BackoffStrategyKind.Fixed,
+        minMs: 10,
+        maxMs: 10,
+      });
+      restored.push("a");
+      expect(restored.length()).toBe(0);
+      expect(restored.scheduledAt("a")).toBeGreaterThan(Date.now());
+    });
+
+    it("will restore snapshots without the newer fields", () => {
+      const restored = QueueWithBackoff.restore(
+        JSON.stringify({
+          queue: ["a"],
+          backoff: { [Date.now() + 60000]: "b" },
+          last_backoff_duration: { b: 500 },
+        }),
+        BACKOFF_TIME_MS,
+        1.05,
+        BACKOFF_MAX_MS,
+      );
+      expect(restored.contains("b")).toBe(true);
+      expect(restored.backoffStatus("b")?.backoffDuration).toBe(500);
+      expect(popAll(restored)).toEqual(["a"]);
+    });
+
+    it("will reject an invalid snapshot", () => {
+      expect(() =>
+        QueueWithBackoff.restore("{", BACKOFF_TIME_MS, 1.05, BACKOFF_MAX_MS),
+      ).toThrow("Could not parse queue snapshot");
+    });
+  });
+});
@@ -0,0 +1,90 @@
+import { describe, it, expect } from "vitest";
+import { RateLimiter } from "../src/libRs";
+
+describe("RateLimiter", () => {
+  it("will allow a burst of requests and then limit", () => {
+    const limiter = new RateLimiter(3, 1);
+    expect(limiter.tryAcquire("a")).toBe(true);
+    expect(limiter.tryAcquire("a", 2)).toBe(true);
+    expect(limiter.tryAcquire("a")).toBe(false);
+    expect(limiter.available("a")).toBeLessThan(1);
+  });
+
+  it("will limit each key separately", () => {
+    const limiter = new RateLimiter(1, 1);
+    expect(limiter.tryAcquire("a")).toBe(true);
+    expect(limiter.tryAcquire("a")).toBe(false);
+    expect(limiter.tryAcquire("b")).toBe(true);
+    expect(limiter.size()).toBe(2);
+  });
+
+  it("will not take tokens when limited", () => {
+    const limiter = new RateLimiter(2, 1);
+    expect(limiter.tryAcquire("a", 3)).toBe(false);
+    expect(limiter.available("a")).toBe(2);
+  });
+
+  it("will report the burst as available for unknown keys", () => {
+    const limiter = new RateLimiter(5, 1);
+    expect(limiter.available("a")).toBe(5);
+    expect(limiter.size()).toBe(0);
+  });
+
+  it("will refill tokens over time", async () => {
+    const limiter = new RateLimiter(1, 100);
+    expect(limiter.tryAcquire("a")).toBe(true);
+    expect(limiter.tryAcquire("a")).toBe(false);
+    await new Promise((r) => setTimeout(r, 20));
+    expect(limiter.tryAcquire("a")).toBe(true);
+  });
+
+  it("will wait for tokens to become available", async () => {
+    const limiter = new RateLimiter(1, 50);
+    const start = Date.now();
+    await limiter.acquire("a");
+    await limiter.acquire("a");
+    await limiter.acquire("a");
+    // Two tokens at 50 per second take 40ms to refill.
+    expect(Date.now() - start).toBeGreaterThanOrEqual(35);
+  });
+
+  it("will serve waiting callers in order", async () => {
+    const limiter = new RateLimiter(1, 100);
+    const order: number[] = [];
+    await Promise.all(
+      [1, 2, 3].map((i) => limiter.acquire("a").then(() => order.push(i))),
+    );
+    expect(order).toEqual([1, 2, 3]);
+  });
+
+  it("will evict idle keys", async () => {
+    const limiter = new RateLimiter(1, 1000, 10);
+    limiter.tryAcquire("a");
+    await new Promise((r) => setTimeout(r, 20));
+    expect(limiter.evictIdle()).toBe(1);
+    expect(limiter.size()).toBe(0);
+  });
+
+  it("will reject invalid token counts", async () => {
+    const limiter = new RateLimiter(5, 1);
+    for (const tokens of [0, -1, NaN, Infinity]) {
+      expect(() => limiter.tryAcquire("a", tokens)).toThrow(
+        "tokens must be a finite number greater than 0",
+      );
+      expect(() => limiter.acquire("a", tokens)).toThrow(
+        "tokens must be a finite number greater than 0",
+      );
+    }
+    expect(() => limiter.acquire("a", 1e20)).toThrow(
+      "tokens would take too long to become available",
+    );
+    expect(limiter.available("a")).toBe(5);
+  });
+
+  it("will reject invalid options", () => {
+    expect(() => new RateLimiter(0, 1)).toThrow("burst must be at least 1");
+    expect(() => new RateLimiter(1, 0)).toThrow(
+      "refillPerSecond must be greater than 0",
+    );
+  });
+});
@@ -0,0 +1,141 @@
+import { describe, it, afterAll, expect } from "vitest";
+import { Server, createServer } from "http";
+import { AddressInfo } from "net";
+import { unfurlLink } from "../src/libRs";
+
+async function constructServer(
+  pages: Record<string, { headers: Record<string, string>; data: string }>,
+) {
+  const httpServer = await new Promise<Server>((resolve) => {
+    const srv = createServer((req, res) => {
+      const page = pages[req.url ?? "/"];
+      if (!page) {
+        res.writeHead(404);
+        res.end();
+        return;
+      }
+      res.writeHead(200, page.headers);
+      res.write(page.data);
+      res.end();
+    }).listen(0, "127.0.0.1", () => {
+      resolve(srv);
+    });
+  });
+  const address = httpServer.address() as AddressInfo;
+  afterAll(() => httpServer.close());
+  return `http://127.0.0.1:${address.port}`;
+}
+
+const html = { "Content-Type": "text/html; charset=utf-8" };
+// The test server listens on a loopback address.
+const options = { userAgent: "hookshot-test", allowPrivateAddresses: true };
+
+describe("unfurlLink", () => {
+  it("should prefer OpenGraph tags", async () => {
+    const baseUrl = await constructServer({
+      "/": {
+        headers: html,
+        data: `<html><head>
+          <title>Page title</title>
+          <meta name="description" content="Meta description">
+          <meta property="og:title" content="OG title">
+          <meta property="og:description" content="OG description">
+          <meta property="og:image" content="/image.png">
+          <meta property="og:site_name" content="Example">
+        </head></html>`,
+      },
+    });
+    const result = await unfurlLink(`${baseUrl}/`, options);
+    expect(result.title).toBe("OG title");
+    expect(result.description).toBe("OG description");
+    expect(result.image).toBe(`${baseUrl}/image.png`);
+    expect(result.siteName).toBe("Example");
+  });
+
+  it("should fall back to Twitter card and meta tags", async () => {
+    const baseUrl = await constructServer({
+      "/": {
+        headers: html,
+        data: `<html><head>
+          <title>Page title</title>
+          <meta name="description" content="Meta description">
+          <meta name="twitter:image" content="https://example.org/card.png">
+        </head></html>`,
+      },
+    });
+    const result = await unfurlLink(`${baseUrl}/`, options);
+    expect(result.title).toBe("Page title");
+    expect(result.description).toBe("Meta description");
+    expect(result.image).toBe("https://example.org/card.png");
+    expect(result.siteName).toBeUndefined();
+  });
+
+  it("should only read up to the body limit", async () => {
+    const baseUrl = await constructServer({
+      "/": {
+        headers: html,
+        data: `<html><head><title>Title</title></head><body>${"a".repeat(4096)}</body>
+          <meta name="description" content="Too late"></html>`,
+      },
+    });
+    const result = await unfurlLink(`${baseUrl}/`, {
+      ...options,
+      maxBodyBytes: 1024,
+    });
+    expect(result.title).toBe("Title");
+    expect(result.description).toBeUndefined();
+  });
+
+  it("should reject non-HTML content", async () => {
+    const baseUrl = await constructServer({
+      "/feed": { headers: { "Content-Type": "application/rss+xml" }, data: "" },
+    });
+    await expect(unfurlLink(`${baseUrl}/feed`, options)).rejects.toThrow(
+      "not a HTML page",
+    );
+  });
+
+  it("should refuse private addresses by default", async () => {
+    const baseUrl = await constructServer({
+      "/": { headers: html, data: "<html><title>Internal</title></html>" },
+    });
+    await expect(
+      unfurlLink(`${baseUrl}/`, { userAgent: "hookshot-test" }),
+    ).rejects.toThrow("Cannot unfurl links to private address 127.0.0.1");
+    await expect(
+      unfurlLink("http://[::1]/", { userAgent: "hookshot-test" }),
+    ).rejects.toThrow("Cannot unfurl links to private address ::1");
+    await expect(
+      unfurlLink(baseUrl.replace("127.0.0.1", "localhost") + "/", {
+        userAgent: "hookshot-test",
+      }),
+    ).rejects.toThrow("resolves to private address");
+  });
+
+  it("should not use environment proxies when refusing private addresses", async () => {
+    const proxyUrl = await constructServer({
+      "http://unfurl-test.invalid/": {
+        headers: html,
+        data: "<html><title>Proxied</title></html>",
+      },
+    });
+    const env = { ...process.env };
+    process.env.HTTP_PROXY = process.env.http_proxy = proxyUrl;
+    process.env.NO_PROXY = process.env.no_proxy = "";
+    try {
+      await expect(
+        unfurlLink("http://unfurl-test.invalid/", {
+          userAgent: "hookshot-test",
+        }),
+      ).rejects.toThrow();
+    } finally {
+      process.env = env;
+    }
+  });
+
+  it("should reject non-HTTP URLs", async () => {
+    await expect(unfurlLink("file:///etc/passwd", options)).rejects.toThrow(
+      "Cannot unfurl URLs with scheme 'file'",
+    );
+  });
+});
@@ -0,0 +1,63 @@
+import { bench, describe } from "vitest";
+import {
+  BridgeConfigActorPermission,
+  BridgePermissions,
+} from "../../src/libRs";
+
+// A config with hundreds of entries, similar to a large deployment.
+function genLargeConfig(): BridgeConfigActorPermission[] {
+  const config: BridgeConfigActorPermission[] = [];
+  for (let i = 0; i < 400; i++) {
+    config.push({
+      actor: `@user${i}:example.com`,
+      services: [
+        { service: "github", level: "login" },
+        { service: "feeds", level: "commands" },
+      ],
+    });
+  }
+  for (let i = 0; i < 100; i++) {
+    config.push({
+      actor: `server${i}.example.org`,
+      services: [{ service: "*", level: "commands" }],
+    });
+  }
+  for (let i = 0; i < 20; i++) {
+    config.push({
+      actor: `!room${i}:example.com`,
+      services: [{ service: "gitlab", level: "manageConnections" }],
+    });
+  }
+  config.push({
+    actor: "*.corp.example.com",
+    services: [{ service: "jira", level: "login" }],
+  });
+  config.push({
+    actor: "@admin:example.com",
+    services: [{ service: "*", level: "admin" }],
+  });
+  return config;
+}
+
+describe("BridgePermissions", () => {
+  const bridgePermissions = new BridgePermissions(genLargeConfig());
+  for (let i = 0; i < 20; i++) {
+    bridgePermissions.addMemberToCache(`!room${i}:example.com`, "@member:bar");
+  }
+
+  bench("checkAction for a user at the end of the config", () => {
+    bridgePermissions.checkAction("@admin:example.com", "github", "admin");
+  });
+
+  bench("checkAction for a user with no permissions", () => {
+    bridgePermissions.checkAction("@nobody:example.net", "github", "login");
+  });
+
+  bench("checkAction for a room member", () => {
+    bridgePermissions.checkAction("@member:bar", "gitlab", "manageConnections");
+  });
+
+  bench("checkActionAny for a domain actor", () => {
+    bridgePermissions.checkActionAny("@someone:server99.example.org", "login");
+  });
+});
@@ -1,5 +1,9 @@
 import { describe, it, expect } from "vitest";
-import { BridgePermissions } from "../../src/libRs";
+import {
+  BridgePermissions,
+  PermissionDiagnosticSeverity,
+  validatePermissionConfig,
+} from "../../src/libRs";
 
 function genBridgePermissions(actor: string, service: string, level: string) {
   return new BridgePermissions([
@@ -141,6 +145,67 @@ describe("Config/BridgePermissions", () => {
       ).toBe(true);
     });
 
+    it("will match user ID glob actors", () => {
+      const bridgePermissions = genBridgePermissions(
+        "@*-bot:example.com",
+        "my-service",
+        "login",
+      );
+      expect(
+        bridgePermissions.checkAction(
+          "@ci-bot:example.com",
+          "my-service",
+          "login",
+        ),
+      ).toBe(true);
+      expect(
+        bridgePermissions.checkAction("@ci:example.com", "my-service", "login"),
+      ).toBe(false);
+      expect(
+        bridgePermissions.checkAction(
+          "@ci-bot:example.com.evil",
+          "my-service",
+          "login",
+        ),
+      ).toBe(false);
+    });
+
+    it("will match domain glob actors", () => {
+      const bridgePermissions = genBridgePermissions(
+        "*.corp.example.com",
+        "my-service",
+        "login",
+      );
+      expect(
+        bridgePermissions.checkAction(
+          "@foo:hs1.corp.example.com",
+          "my-service",
+          "login",
+        ),
+      ).toBe(true);
+      expect(
+        bridgePermissions.checkAction(
+          "@foo:a.b.corp.example.com",
+          "my-service",
+          "login",
+        ),
+      ).toBe(true);
+      expect(
+        bridgePermissions.checkAction(
+          "@foo:corp.example.com",
+          "my-service",
+          "login",
+        ),
+      ).toBe(false);
+      expect(
+        bridgePermissions.checkAction(
+          "@foo:xcorp.example.com",
+          "my-service",
+          "login",
+        ),
+      ).toBe(false);
+    });
+
     it("will return true for a wildcard service", () => {
       const bridgePermissions = genBridgePermissions("@foo:bar", "*", "login");
       expect(
@@ -171,6 +236,86 @@ describe("Config/BridgePermissions", () => {
       ).toBe(true);
     });
 
+    it("will check the power level of room members for power level actors", () => {
+      const bridgePermissions = new BridgePermissions([
+        {
+          actor: "!foo:bar",
+          powerLevel: 50,
+          services: [{ service: "my-service", level: "manageConnections" }],
+        },
+      ]);
+      bridgePermissions.addMemberToCache("!foo:bar", "@mod:bar");
+      bridgePermissions.addMemberToCache("!foo:bar", "@user:bar");
+      bridgePermissions.updatePowerLevels("!foo:bar", {
+        users: { "@mod:bar": 50, "@left:bar": 100 },
+        users_default: 0,
+      });
+      expect(
+        bridgePermissions.checkAction(
+          "@mod:bar",
+          "my-service",
+          "manageConnections",
+        ),
+      ).toBe(true);
+      expect(
+        bridgePermissions.checkAction(
+          "@user:bar",
+          "my-service",
+          "manageConnections",
+        ),
+      ).toBe(false);
+      expect(
+        bridgePermissions.checkAction(
+          "@left:bar",
+          "my-service",
+          "manageConnections",
+        ),
+      ).toBe(false);
+    });
+
+    it("will return true for members of a space's child rooms", () => {
+      const bridgePermissions = new BridgePermissions([
+        {
+          actor: "!space:bar",
+          includeSpaceChildren: true,
+          services: [{ service: "my-service", level: "login" }],
+        },
+      ]);
+      expect(bridgePermissions.getInterestedSpaces()).toEqual(["!space:bar"]);
+      const update = bridgePermissions.setSpaceChildren("!space:bar", [
+        "!child:bar",
+      ]);
+      expect(update.addedRooms).toEqual(["!child:bar"]);
+      expect(bridgePermissions.getInterestedRooms().sort()).toEqual([
+        "!child:bar",
+        "!space:bar",
+      ]);
+      bridgePermissions.addMemberToCache("!child:bar", "@foo:bar");
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "my-service", "login"),
+      ).toBe(true);
+      expect(
+        bridgePermissions.setSpaceChildren("!space:bar", []).removedRooms,
+      ).toEqual(["!child:bar"]);
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "my-service", "login"),
+      ).toBe(false);
+    });
+
+    it("will not allow space children to be combined with a power level", () => {
+      expect(
+        () =>
+          new BridgePermissions([
+            {
+              actor: "!space:bar",
+              includeSpaceChildren: true,
+              powerLevel: 50,
+              services: [{ service: "my-service", level: "login" }],
+            },
+          ]),
+      ).toThrow("cannot combine includeSpaceChildren with powerLevel");
+    });
+
     it("will fall through and return true for multiple permission sets", () => {
       const bridgePermissions = new BridgePermissions([
         {
@@ -209,6 +354,58 @@ describe("Config/BridgePermissions", () => {
       ).toBe(false);
     });
 
+    it("will return false if a matching deny entry covers the level", () => {
+      const bridgePermissions = new BridgePermissions([
+        {
+          actor: "bar",
+          services: [{ service: "my-service", level: "manageConnections" }],
+        },
+        {
+          actor: "@foo:bar",
+          services: [
+            { service: "my-service", level: "manageConnections", deny: true },
+          ],
+        },
+      ]);
+      expect(
+        bridgePermissions.checkAction(
+          "@foo:bar",
+          "my-service",
+          "manageConnections",
+        ),
+      ).toBe(false);
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "my-service", "login"),
+      ).toBe(true);
+      expect(
+        bridgePermissions.checkAction(
+          "@baz:bar",
+          "my-service",
+          "manageConnections",
+        ),
+      ).toBe(true);
+    });
+
+    it("will prefer deny entries regardless of order", () => {
+      const bridgePermissions = new BridgePermissions([
+        {
+          actor: "!foo:bar",
+          services: [{ service: "*", level: "commands", deny: true }],
+        },
+        {
+          actor: "@foo:bar",
+          services: [{ service: "my-service", level: "admin" }],
+        },
+      ]);
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "my-service", "commands"),
+      ).toBe(true);
+      bridgePermissions.addMemberToCache("!foo:bar", "@foo:bar");
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "my-service", "commands"),
+      ).toBe(false);
+    });
+
     it("handles legacy 'webhooks' config field", () => {
       const bridgePermissions = new BridgePermissions([
         {
@@ -226,6 +423,505 @@ describe("Config/BridgePermissions", () => {
     });
   });
 
+  describe("checkActionForTarget", () => {
+    const bridgePermissions = new BridgePermissions([
+      {
+        actor: "@foo:bar",
+        services: [
+          {
+            service: "github",
+            level: "manageConnections",
+            targets: ["github:matrix-org/*", "github:foo/bar"],
+          },
+          {
+            service: "github",
+            level: "manageConnections",
+            targets: ["github:matrix-org/secret"],
+            deny: true,
+          },
+        ],
+      },
+    ]);
+
+    for (const target of [
+      "github:matrix-org/matrix-hookshot",
+      "github:foo/bar",
+    ]) {
+      it(`will return true for a matching target '${target}'`, () => {
+        expect(
+          bridgePermissions.checkActionForTarget(
+            "@foo:bar",
+            "github",
+            "manageConnections",
+            target,
+          ),
+        ).toBe(true);
+      });
+    }
+
+    it("will return false for a target that does not match", () => {
+      expect(
+        bridgePermissions.checkActionForTarget(
+          "@foo:bar",
+          "github",
+          "manageConnections",
+          "github:foo/baz",
+        ),
+      ).toBe(false);
+    });
+
+    it("will return false for a denied target", () => {
+      expect(
+        bridgePermissions.checkActionForTarget(
+          "@foo:bar",
+          "github",
+          "manageConnections",
+          "github:matrix-org/secret",
+        ),
+      ).toBe(false);
+    });
+
+    it("will not count scoped grants for checkAction", () => {
+      expect(
+        bridgePermissions.checkAction(
+          "@foo:bar",
+          "github",
+          "manageConnections",
+        ),
+      ).toBe(false);
+    });
+
+    it("will apply grants without targets to every target", () => {
+      const unscopedPermissions = new BridgePermissions([
+        {
+          actor: "@foo:bar",
+          services: [
+            { service: "github", level: "manageConnections" },
+            {
+              service: "github",
+              level: "manageConnections",
+              targets: ["github:matrix-org/secret"],
+              deny: true,
+            },
+          ],
+        },
+      ]);
+      expect(
+        unscopedPermissions.checkActionForTarget(
+          "@foo:bar",
+          "github",
+          "manageConnections",
+          "github:foo/bar",
+        ),
+      ).toBe(true);
+      expect(
+        unscopedPermissions.checkActionForTarget(
+          "@foo:bar",
+          "github",
+          "manageConnections",
+          "github:matrix-org/secret",
+        ),
+      ).toBe(false);
+    });
+  });
+
+  describe("explainAction", () => {
+    const bridgePermissions = new BridgePermissions([
+      {
+        actor: "bar",
+        services: [{ service: "my-service", level: "login" }],
+      },
+      {
+        actor: "@foo:bar",
+        services: [
+          {
+            service: "my-service",
+            level: "admin",
+            targets: ["my-service:allowed/*"],
+          },
+        ],
+      },
+      {
+        actor: "@baz:bar",
+        services: [{ service: "*", level: "commands", deny: true }],
+      },
+    ]);
+
+    it("will explain which entry granted a permission", () => {
+      const explanation = bridgePermissions.explainAction(
+        "@foo:bar",
+        "my-service",
+        "admin",
+        "my-service:allowed/thing",
+      );
+      expect(explanation.allowed).toBe(true);
+      expect(explanation.entryIndex).toBe(1);
+      expect(explanation.actor).toBe("@foo:bar");
+      expect(explanation.level).toBe("admin");
+    });
+
+    it("will explain the closest entry that fell short", () => {
+      const explanation = bridgePermissions.explainAction(
+        "@foo:bar",
+        "my-service",
+        "admin",
+        "my-service:other/thing",
+      );
+      expect(explanation.allowed).toBe(false);
+      expect(explanation.entryIndex).toBe(0);
+      expect(explanation.level).toBe("login");
+      expect(explanation.reason).toContain("'admin' is required");
+    });
+
+    it("will explain a denial", () => {
+      const explanation = bridgePermissions.explainAction(
+        "@baz:bar",
+        "my-service",
+        "login",
+      );
+      expect(explanation.allowed).toBe(false);
+      expect(explanation.entryIndex).toBe(2);
+      expect(explanation.reason).toContain("denies");
+    });
+
+    it("will explain when no actors match", () => {
+      const explanation = bridgePermissions.explainAction(
+        "@foo:baz",
+        "my-service",
+        "login",
+      );
+      expect(explanation.allowed).toBe(false);
+      expect(explanation.entryIndex).toBeUndefined();
+    });
+
+    it("will explain entries that have expired or not started", () => {
+      const timedPermissions = new BridgePermissions([
+        {
+          actor: "@foo:bar",
+          expires: "2020-01-01T00:00:00Z",
+          services: [{ service: "my-service", level: "admin" }],
+        },
+        {
+          actor: "@bar:bar",
+          notBefore: "2999-01-01",
+          services: [{ service: "my-service", level: "admin" }],
+        },
+      ]);
+      expect(
+        timedPermissions.explainAction("@foo:bar", "my-service", "admin"),
+      ).toMatchObject({
+        allowed: false,
+        entryIndex: 0,
+        level: "admin",
+        reason: "Actor '@foo:bar' expired at 2020-01-01T00:00:00+00:00",
+      });
+      expect(
+        timedPermissions.explainAction("@bar:bar", "my-service", "admin"),
+      ).toMatchObject({
+        allowed: false,
+        entryIndex: 1,
+        reason: "Actor '@bar:bar' only applies from 2999-01-01T00:00:00+00:00",
+      });
+    });
+
+    it("will explain when a room member is below the power level", () => {
+      const roomPermissions = new BridgePermissions([
+        { actor: "bar", services: [{ service: "my-service", level: "login" }] },
+        {
+          actor: "!foo:bar",
+          powerLevel: 50,
+          services: [{ service: "my-service", level: "admin" }],
+        },
+      ]);
+      roomPermissions.addMemberToCache("!foo:bar", "@foo:bar");
+      roomPermissions.updatePowerLevels("!foo:bar", {
+        users: { "@foo:bar": 10 },
+      });
+      expect(
+        roomPermissions.explainAction("@foo:bar", "my-service", "admin"),
+      ).toMatchObject({
+        allowed: false,
+        entryIndex: 1,
+        reason: "Actor '!foo:bar' requires power level 50, but '@foo:bar' has 10",
+      });
+    });
+  });
+
+  describe("roles", () => {
+    const roles = {
+      ops: {
+        services: [{ service: "github", level: "manageConnections" }],
+        roles: ["reader"],
+      },
+      reader: {
+        services: [{ service: "feeds", level: "commands" }],
+      },
+    };
+
+    it("will grant the services of referenced and nested roles", () => {
+      const bridgePermissions = new BridgePermissions(
+        [{ actor: "@foo:bar", roles: ["ops"], services: [] }],
+        roles,
+      );
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "github", "manageConnections"),
+      ).toBe(true);
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "feeds", "commands"),
+      ).toBe(true);
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "jira", "commands"),
+      ).toBe(false);
+    });
+
+    it("will throw for an undefined role", () => {
+      expect(
+        () =>
+          new BridgePermissions(
+            [{ actor: "@foo:bar", roles: ["missing"], services: [] }],
+            roles,
+          ),
+      ).toThrow("role 'missing' is not defined");
+    });
+
+    it("will throw for roles that form a cycle", () => {
+      expect(
+        () =>
+          new BridgePermissions(
+            [{ actor: "@foo:bar", roles: ["a"], services: [] }],
+            { a: { roles: ["b"] }, b: { roles: ["a"] } },
+          ),
+      ).toThrow("roles form a cycle: a -> b -> a");
+    });
+
+    it("will report cycles when validating", () => {
+      expect(
+        validatePermissionConfig([], {
+          a: { roles: ["b"] },
+          b: { roles: ["a"] },
+        }),
+      ).toMatchObject([
+        { severity: PermissionDiagnosticSeverity.Error, role: "a" },
+        { severity: PermissionDiagnosticSeverity.Error, role: "b" },
+      ]);
+    });
+  });
+
+  describe("time bounded permissions", () => {
+    const bridgePermissions = new BridgePermissions([
+      {
+        actor: "@expired:bar",
+        expires: "2020-01-01",
+        services: [{ service: "my-service", level: "login" }],
+      },
+      {
+        actor: "@future:bar",
+        notBefore: "2999-01-01T00:00:00Z",
+        services: [{ service: "my-service", level: "login" }],
+      },
+      {
+        actor: "@current:bar",
+        notBefore: "2020-01-01",
+        expires: "2999-01-01",
+        services: [{ service: "my-service", level: "login" }],
+      },
+    ]);
+
+    it("will only apply entries within their time bounds", () => {
+      expect(
+        bridgePermissions.checkAction("@expired:bar", "my-service", "login"),
+      ).toBe(false);
+      expect(
+        bridgePermissions.checkAction("@future:bar", "my-service", "login"),
+      ).toBe(false);
+      expect(
+        bridgePermissions.checkAction("@current:bar", "my-service", "login"),
+      ).toBe(true);
+      expect(bridgePermissions.checkActionAny("@expired:bar", "login")).toBe(
+        false,
+      );
+    });
+
+    it("will list expiring permissions", () => {
+      expect(bridgePermissions.getExpiringPermissions(60000)).toEqual([
+        {
+          entryIndex: 0,
+          actor: "@expired:bar",
+          expires: "2020-01-01T00:00:00+00:00",
+          expired: true,
+        },
+      ]);
+    });
+
+    it("will list all expiring permissions for very large windows", () => {
+      for (const withinMs of [Number.MAX_SAFE_INTEGER, Infinity]) {
+        expect(
+          bridgePermissions
+            .getExpiringPermissions(withinMs)
+            .map((grant) => grant.actor),
+        ).toEqual(["@expired:bar", "@current:bar"]);
+      }
+    });
+
+    it("will throw for invalid timestamps", () => {
+      expect(
+        () =>
+          new BridgePermissions([
+            {
+              actor: "@foo:bar",
+              expires: "tomorrow",
+              services: [{ service: "my-service", level: "login" }],
+            },
+          ]),
+      ).toThrow("expires 'tomorrow' is not an RFC 3339 timestamp");
+    });
+  });
+
+  describe("checkActionInRoom", () => {
+    const bridgePermissions = new BridgePermissions([
+      {
+        actor: "bar",
+        services: [{ service: "*", level: "manageConnections" }],
+      },
+    ]);
+    bridgePermissions.setRoomOverrides("!room:bar", [
+      {
+        actor: "*",
+        services: [
+          { service: "*", level: "admin" },
+          { service: "generic", level: "commands", deny: true },
+        ],
+      },
+    ]);
+
+    it("will use the config for rooms without overrides", () => {
+      expect(
+        bridgePermissions.checkActionInRoom(
+          "@foo:bar",
+          "!other:bar",
+          "generic",
+          "manageConnections",
+        ),
+      ).toBe(true);
+    });
+
+    it("will apply deny entries from room overrides", () => {
+      expect(
+        bridgePermissions.checkActionInRoom(
+          "@foo:bar",
+          "!room:bar",
+          "generic",
+          "commands",
+        ),
+      ).toBe(false);
+      expect(
+        bridgePermissions.checkActionInRoom(
+          "@foo:bar",
+          "!room:bar",
+          "github",
+          "manageConnections",
+        ),
+      ).toBe(true);
+    });
+
+    it("will not allow room overrides to grant more than the config", () => {
+      expect(
+        bridgePermissions.checkActionInRoom(
+          "@foo:bar",
+          "!room:bar",
+          "github",
+          "admin",
+        ),
+      ).toBe(false);
+    });
+
+    it("will not allow room actors in room overrides", () => {
+      expect(() =>
+        bridgePermissions.setRoomOverrides("!room:bar", [
+          { actor: "!other:bar", services: [] },
+        ]),
+      ).toThrow("cannot use room actors");
+    });
+  });
+
+  describe("updateConfig", () => {
+    it("will keep membership for rooms that are still referenced", () => {
+      const bridgePermissions = new BridgePermissions([
+        {
+          actor: "!foo:bar",
+          services: [{ service: "my-service", level: "login" }],
+        },
+        {
+          actor: "!old:bar",
+          services: [{ service: "my-service", level: "login" }],
+        },
+      ]);
+      bridgePermissions.addMemberToCache("!foo:bar", "@foo:bar");
+      const update = bridgePermissions.updateConfig([
+        {
+          actor: "!foo:bar",
+          services: [{ service: "my-service", level: "admin" }],
+        },
+        {
+          actor: "!new:bar",
+          services: [{ service: "my-service", level: "login" }],
+        },
+      ]);
+      expect(update.addedRooms).toEqual(["!new:bar"]);
+      expect(update.removedRooms).toEqual(["!old:bar"]);
+      expect(
+        bridgePermissions.checkAction("@foo:bar", "my-service", "admin"),
+      ).toBe(true);
+    });
+
+    it("will report tracked rooms that now need power levels or children", () => {
+      const bridgePermissions = new BridgePermissions([
+        {
+          actor: "!foo:bar",
+          services: [{ service: "my-service", level: "login" }],
+        },
+        {
+          actor: "!space:bar",
+          services: [{ service: "my-service", level: "login" }],
+        },
+      ]);
+      const update = bridgePermissions.updateConfig([
+        {
+          actor: "!foo:bar",
+          powerLevel: 50,
+          services: [{ service: "my-service", level: "login" }],
+        },
+        {
+          actor: "!space:bar",
+          includeSpaceChildren: true,
+          services: [{ service: "my-service", level: "login" }],
+        },
+        {
+          actor: "!new:bar",
+          powerLevel: 50,
+          services: [{ service: "my-service", level: "login" }],
+        },
+      ]);
+      expect(update.addedRooms).toEqual(["!new:bar"]);
+      expect(update.addedPowerLevelRooms).toEqual(["!foo:bar"]);
+      expect(update.addedSpaces).toEqual(["!space:bar"]);
+      expect(
+        bridgePermissions.updateConfig([
+          {
+            actor: "!foo:bar",
+            powerLevel: 50,
+            services: [{ service: "my-service", level: "login" }],
+          },
+          {
+            actor: "!space:bar",
+            includeSpaceChildren: true,
+            services: [{ service: "my-service", level: "login" }],
+          },
+        ]),
+      ).toMatchObject({ addedPowerLevelRooms: [], addedSpaces: [] });
+    });
+  });
+
   describe("permissionsCheckActionAny", () => {
     it("will return false for an empty actor set", () => {
       const bridgePermissions = new BridgePermissions([]);
@@ -254,5 +950,92 @@ describe("Config/BridgePermissions", () => {
         );
       });
     }
+
+    it("will ignore grants for services that are denied", () => {
+      const bridgePermissions = new BridgePermissions([
+        {
+          actor: "bar",
+          services: [{ service: "my-service", level: "manageConnections" }],
+        },
+        {
+          actor: "@foo:bar",
+          services: [
+            { service: "my-service", level: "manageConnections", deny: true },
+          ],
+        },
+      ]);
+      expect(
+        bridgePermissions.checkActionAny("@foo:bar", "manageConnections"),
+      ).toBe(false);
+      expect(bridgePermissions.checkActionAny("@foo:bar", "login")).toBe(true);
+    });
+  });
+
+  describe("validatePermissionConfig", () => {
+    it("will return no diagnostics for a valid config", () => {
+      expect(
+        validatePermissionConfig([
+          { actor: "*", services: [{ level: "commands" }] },
+          {
+            actor: "*.example.com:8448",
+            services: [{ service: "github", level: "login" }],
+          },
+          {
+            actor: "@foo:bar",
+            services: [
+              {
+                service: "gitlab",
+                level: "admin",
+                targets: ["gitlab:group/*"],
+              },
+            ],
+          },
+          {
+            actor: "!foo:bar",
+            services: [{ service: "*", level: "manageConnections" }],
+          },
+        ]),
+      ).toEqual([]);
+    });
+
+    it("will report malformed actors and invalid levels as errors", () => {
+      expect(
+        validatePermissionConfig([
+          { actor: "not a domain", services: [{ level: "commands" }] },
+          { actor: "@foo:bar", services: [{ level: "superuser" }] },
+        ]),
+      ).toMatchObject([
+        {
+          severity: PermissionDiagnosticSeverity.Error,
+          entryIndex: 0,
+          field: "actor",
+        },
+        {
+          severity: PermissionDiagnosticSeverity.Error,
+          entryIndex: 1,
+          field: "services[0].level",
+        },
+      ]);
+    });
+
+    it("will warn about unknown services", () => {
+      expect(
+        validatePermissionConfig([
+          {
+            actor: "@foo:bar",
+            services: [
+              { service: "github", level: "login" },
+              { service: "gihtub", level: "login" },
+            ],
+          },
+        ]),
+      ).toMatchObject([
+        {
+          severity: PermissionDiagnosticSeverity.Warning,
+          entryIndex: 0,
+          field: "services[1].service",
+        },
+      ]);
+    });
   });
 });
@@ -172,6 +172,23 @@ describe("GitHubRepoConnection", () => {
     });
   });
 
+  describe("getPermissionTarget", () => {
+    it("will use the lowercased repository as the target", () => {
+      expect(
+        GitHubRepoConnection.getPermissionTarget({
+          org: "Matrix-Org",
+          repo: "Matrix-Hookshot",
+        }),
+      ).toBe("github:matrix-org/matrix-hookshot");
+    });
+
+    it("will not return a target for invalid data", () => {
+      expect(
+        GitHubRepoConnection.getPermissionTarget({ org: "matrix-org" }),
+      ).toBeUndefined();
+    });
+  });
+
   describe("onIssueCreated", () => {
     it("will handle a simple issue", async () => {
       const { connection, intent } = createConnection();
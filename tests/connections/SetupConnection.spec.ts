@@ -0,0 +1,62 @@
+import { describe, it, expect } from "vitest";
+import { SetupConnection } from "../../src/Connections/SetupConnection";
+import { ProvisionConnectionOpts } from "../../src/Connections";
+import { BridgeConfig } from "../../src/config/Config";
+import { DefaultConfigRoot } from "../../src/config/Defaults";
+import { AdminRoom } from "../../src/AdminRoom";
+import { MatrixEvent, MatrixMessageContent } from "../../src/MatrixEvent";
+import { IntentMock } from "../utils/IntentMock";
+
+const ROOM_ID = "!foo:bar";
+
+function createSetupConnection(): [SetupConnection, BridgeConfig, IntentMock] {
+  const config = new BridgeConfig(DefaultConfigRoot);
+  const intent = IntentMock.create("@hookshot:example.com");
+  const connection = new SetupConnection(
+    ROOM_ID,
+    "!hookshot",
+    ["generic"],
+    ["generic"],
+    { config, intent } as unknown as ProvisionConnectionOpts,
+    async () => ({}) as AdminRoom,
+    () => {},
+  );
+  return [connection, config, intent];
+}
+
+function message(body: string): MatrixEvent<MatrixMessageContent> {
+  return {
+    event_id: "$event:bar",
+    origin_server_ts: 0,
+    sender: "@foo:example.com",
+    state_key: undefined,
+    type: "m.room.message",
+    content: { msgtype: "m.text", body },
+  };
+}
+
+describe("SetupConnection", () => {
+  it("will not create a webhook when the room's overrides deny it", async () => {
+    const [connection, config, intent] = createSetupConnection();
+    config.updateRoomPermissionOverrides(ROOM_ID, {
+      permissions: [
+        {
+          actor: "*",
+          services: [
+            { service: "*", level: "admin" },
+            { service: "generic", level: "commands", deny: true },
+          ],
+        },
+      ],
+    });
+    // The sender is permitted by the bridge config, so only the overrides deny this.
+    const handled = await connection.onMessageEvent(
+      message("!hookshot webhook my-hook"),
+      () => true,
+    );
+    expect(handled).toBe(true);
+    intent.expectEventBodyContains(
+      "You are not permitted to provision connections for generic",
+    );
+  });
+});
@@ -0,0 +1,301 @@
+import { describe, it, expect } from "vitest";
+import { parseFeed } from "../../src/libRs";
+
+function calendar(...lines: string[]) {
+  return ["BEGIN:VCALENDAR", "VERSION:2.0", ...lines, "END:VCALENDAR"].join(
+    "\r\n",
+  );
+}
+
+function event(...lines: string[]) {
+  return ["BEGIN:VEVENT", ...lines, "END:VEVENT"].join("\r\n");
+}
+
+/**
+ * 10:00 UTC, the given number of days from today.
+ */
+function daysFromNow(days: number) {
+  const date = new Date();
+  date.setUTCHours(10, 0, 0, 0);
+  date.setUTCDate(date.getUTCDate() + days);
+  return date;
+}
+
+function icsTime(date: Date) {
+  return date.toISOString().replace(/[-:]/g, "").replace(/\.\d{3}/, "");
+}
+
+function startsOf(ics: string) {
+  return parseFeed(ics).items.map((item) => new Date(item.start!));
+}
+
+describe("parseFeed with iCalendar feeds", () => {
+  it("will unfold and unescape properties", () => {
+    const feed = parseFeed(
+      calendar(
+        "X-WR-CALNAME:Team calendar",
+        event(
+          "UID:1",
+          "SUMMARY:Weekly",
+          "  sync",
+          "DESCRIPTION:Agenda:\\n- Notes\\, actions",
+          "DTSTART:20240701T090000Z",
+        ),
+      ),
+    );
+    expect(feed.title).toBe("Team calendar");
+    expect(feed.items[0].title).toBe("Weekly sync");
+    expect(feed.items[0].summary).toBe("Agenda:\n- Notes, actions");
+  });
+
+  it("will convert times with a TZID to UTC", () => {
+    const feed = parseFeed(
+      calendar(
+        event("UID:1", "DTSTART;TZID=Europe/London:20240701T090000"),
+        event("UID:2", "DTSTART;TZID=Europe/London:20240115T090000"),
+      ),
+    );
+    expect(feed.items.map((item) => item.start)).toEqual([
+      "2024-07-01T08:00:00+00:00",
+      "2024-01-15T09:00:00+00:00",
+    ]);
+  });
+
+  it("will convert times with a Windows TZID to UTC", () => {
+    const feed = parseFeed(
+      calendar(
+        event("UID:1", 'DTSTART;TZID="Pacific Standard Time":20240115T090000'),
+        event("UID:2", "DTSTART;TZID=Pacific Standard Time:20240701T090000"),
+      ),
+    );
+    expect(feed.items.map((item) => item.start)).toEqual([
+      "2024-01-15T17:00:00+00:00",
+      "2024-07-01T16:00:00+00:00",
+    ]);
+  });
+
+  it("will use VTIMEZONE definitions for unknown TZIDs", () => {
+    const feed = parseFeed(
+      calendar(
+        "BEGIN:VTIMEZONE",
+        "TZID:(UTC-05:00) Eastern Time (US & Canada)",
+        "BEGIN:STANDARD",
+        "DTSTART:16010101T020000",
+        "TZOFFSETFROM:-0400",
+        "TZOFFSETTO:-0500",
+        "RRULE:FREQ=YEARLY;BYDAY=1SU;BYMONTH=11",
+        "END:STANDARD",
+        "BEGIN:DAYLIGHT",
+        "DTSTART:16010101T020000",
+        "TZOFFSETFROM:-0500",
+        "TZOFFSETTO:-0400",
+        "RRULE:FREQ=YEARLY;BYDAY=2SU;BYMONTH=3",
+        "END:DAYLIGHT",
+        "END:VTIMEZONE",
+        event(
+          "UID:1",
+          'DTSTART;TZID="(UTC-05:00) Eastern Time (US & Canada)":20240115T090000',
+        ),
+        event(
+          "UID:2",
+          'DTSTART;TZID="(UTC-05:00) Eastern Time (US & Canada)":20240701T090000',
+        ),
+      ),
+    );
+    expect(feed.items.map((item) => item.start)).toEqual([
+      "2024-01-15T14:00:00+00:00",
+      "2024-07-01T13:00:00+00:00",
+    ]);
+  });
+
+  it("will report all day events as dates", () => {
+    const feed = parseFeed(
+      calendar(
+        event(
+          "UID:1",
+          "DTSTART;VALUE=DATE:20240701",
+          "DTEND;VALUE=DATE:20240702",
+        ),
+      ),
+    );
+    expect(feed.items[0].start).toBe("2024-07-01");
+    expect(feed.items[0].end).toBe("2024-07-02");
+  });
+
+  it("will give events without a UID distinct ids", () => {
+    const feed = parseFeed(
+      calendar(
+        event("SUMMARY:First", "DTSTART:20240701T090000Z"),
+        event("SUMMARY:Second", "DTSTART:20240701T090000Z"),
+        event("SUMMARY:First", "DTSTART:20240702T090000Z"),
+      ),
+    );
+    expect(new Set(feed.items.map((item) => item.hashId)).size).toBe(3);
+  });
+
+  it("will expand weekly rules with BYDAY", () => {
+    const starts = startsOf(
+      calendar(
+        event(
+          "UID:1",
+          "DTSTART:20200106T100000Z",
+          "RRULE:FREQ=WEEKLY;BYDAY=MO,WE",
+        ),
+      ),
+    );
+    expect(starts.length).toBeGreaterThanOrEqual(8);
+    for (const start of starts) {
+      expect([1, 3]).toContain(start.getUTCDay());
+      expect(start.getTime()).toBeGreaterThanOrEqual(Date.now());
+    }
+  });
+
+  it("will expand monthly rules with negative BYDAY ordinals", () => {
+    const starts = startsOf(
+      calendar(
+        event(
+          "UID:1",
+          "DTSTART:20200124T100000Z",
+          "RRULE:FREQ=MONTHLY;BYDAY=-1FR,-2FR",
+        ),
+      ),
+    );
+    expect(starts.length).toBeGreaterThanOrEqual(1);
+    for (const start of starts) {
+      expect(start.getUTCDay()).toBe(5);
+      const inTwoWeeks = new Date(start.getTime() + 14 * 24 * 60 * 60 * 1000);
+      expect(inTwoWeeks.getUTCMonth()).not.toBe(start.getUTCMonth());
+    }
+  });
+
+  it("will count occurrences before the window towards COUNT", () => {
+    expect(
+      startsOf(
+        calendar(
+          event(
+            "UID:1",
+            `DTSTART:${icsTime(daysFromNow(-1500))}`,
+            "RRULE:FREQ=DAILY;COUNT=2000",
+          ),
+        ),
+      ),
+    ).toHaveLength(30);
+    expect(
+      startsOf(
+        calendar(
+          event(
+            "UID:1",
+            `DTSTART:${icsTime(daysFromNow(-100))}`,
+            "RRULE:FREQ=DAILY;COUNT=10",
+          ),
+        ),
+      ),
+    ).toHaveLength(0);
+  });
+
+  it("will stop expanding at UNTIL", () => {
+    const until = daysFromNow(5);
+    const starts = startsOf(
+      calendar(
+        event(
+          "UID:1",
+          `DTSTART:${icsTime(daysFromNow(-10))}`,
+          `RRULE:FREQ=DAILY;UNTIL=${icsTime(until)}`,
+        ),
+      ),
+    );
+    expect(starts[starts.length - 1].getTime()).toBe(until.getTime());
+  });
+
+  it("will skip occurrences listed in EXDATE", () => {
+    const excluded = daysFromNow(2);
+    const starts = startsOf(
+      calendar(
+        event(
+          "UID:1",
+          `DTSTART:${icsTime(daysFromNow(-10))}`,
+          "RRULE:FREQ=DAILY",
+          `EXDATE:${icsTime(excluded)}`,
+        ),
+      ),
+    );
+    expect(starts).toHaveLength(29);
+    expect(starts.map((start) => start.getTime())).not.toContain(
+      excluded.getTime(),
+    );
+  });
+
+  it("will replace occurrences overridden with RECURRENCE-ID", () => {
+    const moved = daysFromNow(2);
+    const feed = parseFeed(
+      calendar(
+        event(
+          "UID:1",
+          "SUMMARY:Standup",
+          `DTSTART:${icsTime(daysFromNow(-10))}`,
+          "RRULE:FREQ=DAILY",
+        ),
+        event(
+          "UID:1",
+          "SUMMARY:Moved standup",
+          `RECURRENCE-ID:${icsTime(moved)}`,
+          `DTSTART:${icsTime(new Date(moved.getTime() + 60 * 60 * 1000))}`,
+        ),
+      ),
+    );
+    expect(feed.items).toHaveLength(30);
+    const starts = feed.items.map((item) => new Date(item.start!).getTime());
+    expect(starts).not.toContain(moved.getTime());
+    expect(
+      feed.items.filter((item) => item.title === "Moved standup"),
+    ).toHaveLength(1);
+    expect(new Set(feed.items.map((item) => item.hashId)).size).toBe(30);
+  });
+
+  it("will ignore durations that are out of range", () => {
+    const feed = parseFeed(
+      calendar(
+        event("UID:1", "DTSTART:20240701T090000Z", "DURATION:P99999999999999W"),
+        event("UID:2", "DTSTART:20240701T090000Z", "DURATION:P100000000D"),
+      ),
+    );
+    expect(feed.items.map((item) => item.end)).toEqual([undefined, undefined]);
+  });
+
+  it("will stop expanding rules that run out of range", () => {
+    expect(
+      startsOf(
+        calendar(
+          event(
+            "UID:1",
+            "DTSTART:20200106T100000Z",
+            "RRULE:FREQ=DAILY;INTERVAL=100000000",
+          ),
+          event(
+            "UID:2",
+            "DTSTART:20200106T100000Z",
+            "RRULE:FREQ=WEEKLY;INTERVAL=100000000",
+          ),
+          event(
+            "UID:3",
+            "DTSTART:20200106T100000Z",
+            "RRULE:FREQ=YEARLY;INTERVAL=4294967295",
+          ),
+        ),
+      ),
+    ).toEqual([]);
+  });
+
+  it("will ignore rules with malformed weekdays", () => {
+    const feed = parseFeed(
+      calendar(
+        event("UID:1", "DTSTART:20240701T090000Z", "RRULE:FREQ=WEEKLY;BYDAY=éa"),
+        event("UID:2", "DTSTART:20240701T090000Z", "RRULE:FREQ=WEEKLY;BYDAY=é"),
+      ),
+    );
+    expect(feed.items.map((item) => item.start)).toEqual([
+      "2024-07-01T09:00:00+00:00",
+      "2024-07-01T09:00:00+00:00",
+    ]);
+  });
+});
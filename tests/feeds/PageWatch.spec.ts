@@ -0,0 +1,116 @@
+import { describe, it, afterAll, expect } from "vitest";
+import { Server, createServer } from "http";
+import { AddressInfo } from "net";
+import { readPageWatch } from "../../src/libRs";
+
+const page = {
+  content: "",
+  etag: "",
+};
+
+async function constructServer() {
+  const httpServer = await new Promise<Server>((resolve) => {
+    const srv = createServer((req, res) => {
+      if (page.etag && req.headers["if-none-match"] === page.etag) {
+        res.writeHead(304);
+        res.end();
+        return;
+      }
+      res.writeHead(200, {
+        "Content-Type": "text/html",
+        ...(page.etag ? { ETag: page.etag } : {}),
+      });
+      res.write(
+        `<html><head><title>Status</title></head><body><main>${page.content}</main><footer>Footer</footer></body></html>`,
+      );
+      res.end();
+    }).listen(0, "127.0.0.1", () => {
+      resolve(srv);
+    });
+  });
+  const address = httpServer.address() as AddressInfo;
+  afterAll(() => httpServer.close());
+  return `http://127.0.0.1:${address.port}/`;
+}
+
+const options = { pollTimeoutSeconds: 5, userAgent: "hookshot-test" };
+
+describe("readPageWatch", () => {
+  it("will not report a change on the first read", async () => {
+    const url = await constructServer();
+    page.content = "<p>All   systems</p><p>operational</p>";
+    page.etag = "";
+    const result = await readPageWatch(url, { selector: "main" }, options);
+    expect(result.feed).toBeUndefined();
+    expect(result.text).toBe("All systems\noperational");
+  });
+
+  it("will report a change to the selected content", async () => {
+    const url = await constructServer();
+    page.content = "<p>Degraded</p>";
+    page.etag = "";
+    const result = await readPageWatch(
+      url,
+      { selector: "main", previousText: "Operational" },
+      options,
+    );
+    expect(result.text).toBe("Degraded");
+    expect(result.feed?.items).toHaveLength(1);
+    expect(result.feed?.items[0]).toMatchObject({
+      title: "Status has changed",
+      link: url,
+      summary: "- Operational\n+ Degraded",
+    });
+  });
+
+  it("will not report a change outside of the selected content", async () => {
+    const url = await constructServer();
+    page.content = "<p>Operational</p>";
+    page.etag = "";
+    const result = await readPageWatch(
+      url,
+      { selector: "main", previousText: "Operational" },
+      options,
+    );
+    expect(result.feed).toBeUndefined();
+  });
+
+  it("will give each change a distinct id, even when content repeats", async () => {
+    const url = await constructServer();
+    page.etag = "";
+    const hashIds = [];
+    let previousText = "A";
+    for (const content of ["B", "A", "B"]) {
+      page.content = content;
+      const result = await readPageWatch(
+        url,
+        { selector: "main", previousText },
+        options,
+      );
+      hashIds.push(result.feed?.items[0].hashId);
+      previousText = result.text!;
+    }
+    expect(new Set(hashIds).size).toBe(3);
+  });
+
+  it("will return the previous text if the page was not modified", async () => {
+    const url = await constructServer();
+    page.content = "<p>Changed</p>";
+    page.etag = '"v1"';
+    const result = await readPageWatch(
+      url,
+      { selector: "main", previousText: "Operational" },
+      { ...options, etag: '"v1"' },
+    );
+    expect(result.feed).toBeUndefined();
+    expect(result.text).toBe("Operational");
+  });
+
+  it("will reject if the selector does not match", async () => {
+    const url = await constructServer();
+    page.etag = "";
+    await expect(
+      readPageWatch(url, { selector: "#missing" }, options),
+    ).rejects.toThrow("Selector did not match any content on the page");
+  });
+});
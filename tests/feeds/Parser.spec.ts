@@ -0,0 +1,124 @@
+import { describe, it, expect } from "vitest";
+import {
+  FeedItem,
+  FeedItemState,
+  classifyFeedItems,
+  parseFeed,
+} from "../../src/libRs";
+
+function feedItem(hashId: string, contentHash?: string): FeedItem {
+  return { hashId, contentHash, idIsPermalink: false };
+}
+
+describe("classifyFeedItems", () => {
+  it("will classify items that have not been seen as new", () => {
+    expect(
+      classifyFeedItems([feedItem("md5:a", "md5:1")], { "md5:b": "md5:1" }),
+    ).toEqual([
+      { hashId: "md5:a", contentHash: "md5:1", state: FeedItemState.New },
+    ]);
+  });
+
+  it("will classify items with a matching content hash as unchanged", () => {
+    expect(
+      classifyFeedItems([feedItem("md5:a", "md5:1")], { "md5:a": "md5:1" }),
+    ).toEqual([
+      {
+        hashId: "md5:a",
+        contentHash: "md5:1",
+        state: FeedItemState.Unchanged,
+      },
+    ]);
+  });
+
+  it("will classify items with a different content hash as updated", () => {
+    expect(
+      classifyFeedItems([feedItem("md5:a", "md5:2")], { "md5:a": "md5:1" }),
+    ).toEqual([
+      { hashId: "md5:a", contentHash: "md5:2", state: FeedItemState.Updated },
+    ]);
+  });
+
+  it("will treat entries stored without a content hash as unchanged", () => {
+    expect(
+      classifyFeedItems([feedItem("md5:a", "md5:2")], { "md5:a": null }),
+    ).toEqual([
+      {
+        hashId: "md5:a",
+        contentHash: "md5:2",
+        state: FeedItemState.Unchanged,
+      },
+    ]);
+  });
+
+  it("will classify items without a hash id as new", () => {
+    expect(
+      classifyFeedItems([{ idIsPermalink: false }], { "md5:a": "md5:1" }),
+    ).toMatchObject([{ state: FeedItemState.New }]);
+  });
+});
+
+describe("parseFeed with podcast feeds", () => {
+  const podcast = (duration: string) => `<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">
+  <channel>
+    <title>Podcast</title>
+    <itunes:image href="https://example.org/show.png"/>
+    <itunes:explicit>yes</itunes:explicit>
+    <item>
+      <title>Episode 3</title>
+      <guid>episode-3</guid>
+      <enclosure url="https://example.org/3.mp3" length="1" type="audio/mpeg"/>
+      <itunes:duration>${duration}</itunes:duration>
+      <itunes:season>2</itunes:season>
+      <itunes:episode>3</itunes:episode>
+      <itunes:image href="https://example.org/3.png"/>
+      <itunes:explicit>clean</itunes:explicit>
+    </item>
+  </channel>
+</rss>`;
+
+  it("will read channel artwork and explicit flag", () => {
+    const feed = parseFeed(podcast("60"));
+    expect(feed.image).toBe("https://example.org/show.png");
+    expect(feed.explicit).toBe(true);
+  });
+
+  it("will read episode metadata", () => {
+    expect(parseFeed(podcast("1:02:03")).items[0].podcast).toEqual({
+      durationSeconds: 3723,
+      season: 2,
+      episode: 3,
+      image: "https://example.org/3.png",
+      explicit: false,
+      enclosureUrl: "https://example.org/3.mp3",
+    });
+  });
+
+  it("will parse durations given in seconds and minutes", () => {
+    expect(parseFeed(podcast("90")).items[0].podcast?.durationSeconds).toBe(
+      90,
+    );
+    expect(
+      parseFeed(podcast("02:30")).items[0].podcast?.durationSeconds,
+    ).toBe(150);
+  });
+
+  it("will ignore durations that overflow", () => {
+    expect(
+      parseFeed(podcast("4294967295:59")).items[0].podcast?.durationSeconds,
+    ).toBeUndefined();
+    expect(
+      parseFeed(podcast("71582788:16")).items[0].podcast?.durationSeconds,
+    ).toBeUndefined();
+  });
+
+  it("will not set podcast metadata for other feeds", () => {
+    const feed = parseFeed(`<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0"><channel><title>Blog</title>
+<item><title>Post</title><guid>post</guid></item>
+</channel></rss>`);
+    expect(feed.items[0].podcast).toBeUndefined();
+    expect(feed.image).toBeUndefined();
+  });
+});
@@ -77,6 +77,18 @@ export class MatrixClientMock {
     return `event_${this.sentEvents.length - 1}`;
   }
 
+  async sendEvent(
+    roomId: string,
+    eventType: string,
+    content: Record<string, unknown>,
+  ): Promise<string> {
+    // Only messages are recorded, so that reactions don't need a body.
+    if (eventType !== "m.room.message") {
+      return `$${eventType}_sent`;
+    }
+    return this.sendMessage(roomId, content);
+  }
+
   async sendStateEvent(): Promise<string> {
     return `$state_event_sent`;
   }
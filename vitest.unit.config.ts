@@ -9,5 +9,8 @@ export default defineConfig({
       enabled: true,
     },
     include: ['tests/**/*.spec.ts'],
+    benchmark: {
+      include: ['tests/**/*.bench.ts'],
+    },
   },
 })